@@ -6,13 +6,38 @@
 //! Nothing here touches C++ or Qt — pure Rust Wayland via `wayland-client`
 //! and `wayland-protocols-wlr`.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
 
 use wayland_client::{
-    Connection, Dispatch, QueueHandle,
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
     backend::ObjectId,
     globals::GlobalListContents,
-    protocol::{wl_compositor, wl_registry, wl_surface},
+    protocol::{wl_callback, wl_compositor, wl_output, wl_registry, wl_seat, wl_surface},
+};
+use wayland_protocols::{
+    ext::idle_notify::v1::client::{
+        ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+        ext_idle_notifier_v1::{self, ExtIdleNotifierV1},
+    },
+    wp::{
+        fractional_scale::v1::client::{
+            wp_fractional_scale_manager_v1::{self, WpFractionalScaleManagerV1},
+            wp_fractional_scale_v1::{self, WpFractionalScaleV1},
+        },
+        presentation_time::client::{
+            wp_presentation::{self, WpPresentation},
+            wp_presentation_feedback::{self, WpPresentationFeedback},
+        },
+        viewporter::client::{
+            wp_viewport::{self, WpViewport},
+            wp_viewporter::{self, WpViewporter},
+        },
+    },
+    xdg::xdg_output::zv1::client::{
+        zxdg_output_manager_v1::{self, ZxdgOutputManagerV1},
+        zxdg_output_v1::{self, ZxdgOutputV1},
+    },
 };
 use wayland_protocols_wlr::{
     foreign_toplevel::v1::client::{
@@ -23,29 +48,210 @@ use wayland_protocols_wlr::{
         zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
         zwlr_layer_surface_v1::{self, Anchor, ZwlrLayerSurfaceV1},
     },
+    output_power_management::v1::client::{
+        zwlr_output_power_manager_v1::{self, ZwlrOutputPowerManagerV1},
+        zwlr_output_power_v1::{self, ZwlrOutputPowerV1},
+    },
 };
 
-pub struct State {
-    pub compositor: Option<wl_compositor::WlCompositor>,
-    pub layer_shell: Option<ZwlrLayerShellV1>,
-    pub toplevel_mgr: Option<ZwlrForeignToplevelManagerV1>,
+/// A bound `wl_output` and the wallpaper layer surface placed on it.
+pub struct Output {
+    pub wl_output: wl_output::WlOutput,
+    /// Only used for the connector name on compositors without `wl_output` v4.
+    pub xdg_output: Option<ZxdgOutputV1>,
+
+    /// Connector name, e.g. `DP-1` or `HDMI-A-1`.
+    pub name: Option<String>,
+    /// Human-readable monitor description (make, model, connector).
+    pub description: Option<String>,
+    /// Set once the first `wl_output.done` has arrived, i.e. the name is known.
+    pub ready: bool,
+    /// Set when the compositor removes the `wl_output` global (unplugged).
+    pub removed: bool,
 
     pub surface: Option<wl_surface::WlSurface>,
     pub layer_surface: Option<ZwlrLayerSurfaceV1>,
-
-    pub buf_w: i32,
-    pub buf_h: i32,
+    /// Present when the compositor supports fractional scaling; the viewport
+    /// maps the physical-size buffer onto the logical surface size.
+    pub viewport: Option<WpViewport>,
+    pub fractional_scale: Option<WpFractionalScaleV1>,
+    /// Preferred scale from `wp_fractional_scale_v1`, in 120ths.
+    pub preferred_scale: Option<u32>,
+
+    /// Surface size in logical pixels, from the last layer-surface configure.
+    pub width: i32,
+    pub height: i32,
+    /// Integer scale and transform advertised by the `wl_output`.
+    pub scale: i32,
+    pub transform: wl_output::Transform,
     pub configured: bool,
+    /// Set when the compositor sends `closed` for this output's layer surface.
+    pub closed: bool,
+    /// When the outstanding `wl_surface.frame` callback was requested; `None`
+    /// once the compositor asked for the next frame.
+    pub frame_requested: Option<Instant>,
+    /// `wp_presentation`, if the compositor has it, and the clock its
+    /// timestamps are on.
+    pub presentation: Option<WpPresentation>,
+    pub clock: libc::clockid_t,
+    /// When the last frame reached the screen, on `clock`.
+    pub last_presented: Option<Duration>,
+    /// Refresh period; `None` until known and on variable refresh rate.
+    pub refresh: Option<Duration>,
+
+    /// Fullscreen windows, and activated or maximized windows, on this
+    /// output.
+    pub fullscreen_count: u32,
+    pub active_count: u32,
+
+    /// Power mode events, when the compositor offers them.
+    power: Option<ZwlrOutputPowerV1>,
+    /// The compositor turned the monitor off, e.g. for DPMS.
+    pub powered_off: bool,
+}
+
+impl Output {
+    /// Size of the decoded video frames: the surface size in physical
+    /// pixels, upright as seen on screen.
+    pub fn frame_size(&self) -> (i32, i32) {
+        match self.preferred_scale {
+            // Rounded as the fractional-scale protocol prescribes.
+            Some(scale) if self.viewport.is_some() => {
+                let scale = scale as i32;
+                (
+                    (self.width * scale + 60) / 120,
+                    (self.height * scale + 60) / 120,
+                )
+            }
+            _ => (self.width * self.scale, self.height * self.scale),
+        }
+    }
+
+    /// `wl_surface` buffer scale; must stay 1 while a viewport does the scaling.
+    pub fn buffer_scale(&self) -> i32 {
+        match self.preferred_scale {
+            Some(_) if self.viewport.is_some() => 1,
+            _ => self.scale,
+        }
+    }
+
+    /// Set buffer scale, buffer transform and viewport destination.  All of
+    /// them are double-buffered and land with the next presented buffer.
+    pub fn apply_buffer_geometry(&self) {
+        if let Some(surface) = &self.surface {
+            surface.set_buffer_scale(self.buffer_scale());
+            surface.set_buffer_transform(self.transform);
+        }
+        if let Some(viewport) = &self.viewport {
+            viewport.set_destination(self.width, self.height);
+        }
+    }
+
+    /// Ask to be told when the compositor wants the next frame and when
+    /// this one was shown.  Must be called before the frame is presented,
+    /// which commits the requests.
+    pub fn request_frame(&mut self, qh: &QueueHandle<State>, name: u32) {
+        if let Some(surface) = &self.surface {
+            surface.frame(qh, name);
+            if let Some(presentation) = &self.presentation {
+                presentation.feedback(surface, qh, name);
+            }
+            self.frame_requested = Some(Instant::now());
+        }
+    }
+
+    /// Time from now until the vblank a frame committed now lands on,
+    /// extrapolated from the last presentation.  Zero when unknown, e.g.
+    /// without `wp_presentation` or on variable refresh rate.
+    pub fn until_vblank(&self) -> Duration {
+        let (Some(last), Some(period)) = (self.last_presented, self.refresh) else {
+            return Duration::ZERO;
+        };
+        let now = clock_now(self.clock);
+        let Some(since) = now.checked_sub(last) else {
+            return last - now;
+        };
+        let periods = (since.as_nanos() / period.as_nanos()) as u32 + 1;
+        (last + period * periods) - now
+    }
+
+    /// Whether the compositor is ready for a new frame.
+    pub fn wants_frame(&self) -> bool {
+        self.frame_requested.is_none()
+    }
+
+    /// Size of the buffers attached to the surface.  The buffer transform is
+    /// set to the output transform, so for rotated outputs these are in the
+    /// panel's native orientation and the compositor can scan them out as-is.
+    pub fn buffer_size(&self) -> (i32, i32) {
+        let (w, h) = self.frame_size();
+        match self.transform {
+            wl_output::Transform::_90
+            | wl_output::Transform::_270
+            | wl_output::Transform::Flipped90
+            | wl_output::Transform::Flipped270 => (h, w),
+            _ => (w, h),
+        }
+    }
+}
+
+/// Current time on `clock`, one of the `CLOCK_*` ids.
+fn clock_now(clock: libc::clockid_t) -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(clock, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// What pausing, muting and window rules need to know about a foreign
+/// toplevel.
+#[derive(Default)]
+pub struct Toplevel {
+    pub app_id: String,
+    pub title: String,
+    /// Fullscreen and not minimized.  It covers its outputs whether or not
+    /// it has focus: the focus may be on another output.
+    pub fullscreen: bool,
+    /// Activated or maximized.
+    pub active: bool,
+    /// Registry names of the outputs it is on.  Until the compositor says,
+    /// it counts as being on all of them; once it has, an empty list means
+    /// none of ours.
+    outputs: Option<Vec<u32>>,
+}
 
-    // Maps foreign-toplevel ObjectId → (was_fullscreen_active, was_active_or_maximized)
-    toplevel_states: HashMap<ObjectId, (bool, bool)>,
-    pub fullscreen_count: i32,
-    pub paused_for_fs: bool,
+impl Toplevel {
+    /// Whether the window is on output `name`.
+    pub fn is_on(&self, name: u32) -> bool {
+        self.outputs.as_ref().is_none_or(|o| o.contains(&name))
+    }
+}
 
-    // Window activity tracking for audio muting and video pausing
-    pub active_or_maximized_count: i32,
-    pub muted_for_windows: bool,
-    pub paused_for_windows: bool,
+pub struct State {
+    pub compositor: Option<wl_compositor::WlCompositor>,
+    pub layer_shell: Option<ZwlrLayerShellV1>,
+    pub toplevel_mgr: Option<ZwlrForeignToplevelManagerV1>,
+    pub xdg_output_mgr: Option<ZxdgOutputManagerV1>,
+    pub output_power_mgr: Option<ZwlrOutputPowerManagerV1>,
+    pub fractional_scale_mgr: Option<WpFractionalScaleManagerV1>,
+    pub viewporter: Option<WpViewporter>,
+    pub presentation: Option<WpPresentation>,
+    /// Clock of `wp_presentation` timestamps.
+    pub presentation_clock: libc::clockid_t,
+    pub seat: Option<wl_seat::WlSeat>,
+    pub idle_notifier: Option<ExtIdleNotifierV1>,
+    /// The notification for the current idle timeout, and that timeout.
+    idle_notification: Option<(ExtIdleNotificationV1, Duration)>,
+    /// Whether the seat has been idle for the timeout.
+    pub idle: bool,
+
+    // Keyed by the wl_registry global name of each wl_output.  A BTreeMap
+    // keeps iteration in advertisement order, so the first output is stable.
+    pub outputs: BTreeMap<u32, Output>,
+
+    toplevels: HashMap<ObjectId, Toplevel>,
 
     pub running: bool,
 }
@@ -56,22 +262,80 @@ impl State {
             compositor: None,
             layer_shell: None,
             toplevel_mgr: None,
-            surface: None,
-            layer_surface: None,
-            buf_w: 0,
-            buf_h: 0,
-            configured: false,
-            toplevel_states: HashMap::new(),
-            fullscreen_count: 0,
-            paused_for_fs: false,
-            active_or_maximized_count: 0,
-            muted_for_windows: false,
-            paused_for_windows: false,
+            xdg_output_mgr: None,
+            output_power_mgr: None,
+            fractional_scale_mgr: None,
+            viewporter: None,
+            presentation: None,
+            presentation_clock: libc::CLOCK_MONOTONIC,
+            seat: None,
+            idle_notifier: None,
+            idle_notification: None,
+            idle: false,
+            outputs: BTreeMap::new(),
+            toplevels: HashMap::new(),
             running: true,
         }
     }
 
-    pub fn create_layer_surface(&mut self, qh: &QueueHandle<State>) -> bool {
+    /// Bind the `wl_output` global `name` and start tracking it.
+    pub fn add_output(
+        &mut self,
+        registry: &wl_registry::WlRegistry,
+        name: u32,
+        version: u32,
+        qh: &QueueHandle<State>,
+    ) {
+        let wl_output = registry.bind::<wl_output::WlOutput, _, _>(name, version.min(4), qh, name);
+        // wl_output v4 reports the connector name itself; older compositors
+        // only expose it through xdg-output.
+        let xdg_output = match &self.xdg_output_mgr {
+            Some(mgr) if version < 4 => Some(mgr.get_xdg_output(&wl_output, qh, name)),
+            _ => None,
+        };
+        let power = self
+            .output_power_mgr
+            .as_ref()
+            .map(|mgr| mgr.get_output_power(&wl_output, qh, name));
+        self.outputs.insert(
+            name,
+            Output {
+                wl_output,
+                xdg_output,
+                name: None,
+                description: None,
+                ready: false,
+                removed: false,
+                surface: None,
+                layer_surface: None,
+                viewport: None,
+                fractional_scale: None,
+                preferred_scale: None,
+                width: 0,
+                height: 0,
+                scale: 1,
+                transform: wl_output::Transform::Normal,
+                configured: false,
+                closed: false,
+                frame_requested: None,
+                presentation: None,
+                clock: libc::CLOCK_MONOTONIC,
+                last_presented: None,
+                refresh: None,
+                fullscreen_count: 0,
+                active_count: 0,
+                power,
+                powered_off: false,
+            },
+        );
+        self.recount_windows();
+    }
+
+    /// Create the background layer surface for the output with global `name`.
+    pub fn create_layer_surface(&mut self, qh: &QueueHandle<State>, name: u32) -> bool {
+        let Some(output) = self.outputs.get_mut(&name) else {
+            return false;
+        };
         let compositor = match &self.compositor {
             Some(c) => c,
             None => {
@@ -92,11 +356,11 @@ impl State {
         let surface = compositor.create_surface(qh, ());
         let layer_surface = layer_shell.get_layer_surface(
             &surface,
-            None, // output: None = compositor picks
+            Some(&output.wl_output),
             zwlr_layer_shell_v1::Layer::Background,
             "wallpaper".to_owned(),
             qh,
-            (),
+            name,
         );
 
         layer_surface.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
@@ -105,67 +369,133 @@ impl State {
         layer_surface
             .set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::None);
 
+        // Fractional scaling needs both: the preferred scale tells us the
+        // physical size, the viewport maps that buffer back to logical size.
+        if let (Some(mgr), Some(viewporter)) = (&self.fractional_scale_mgr, &self.viewporter) {
+            output.fractional_scale = Some(mgr.get_fractional_scale(&surface, qh, name));
+            output.viewport = Some(viewporter.get_viewport(&surface, qh, ()));
+        }
+
         surface.commit();
 
-        self.surface = Some(surface);
-        self.layer_surface = Some(layer_surface);
+        // The clock id arrives right after binding, before any surface.
+        output.presentation = self.presentation.clone();
+        output.clock = self.presentation_clock;
+        output.surface = Some(surface);
+        output.layer_surface = Some(layer_surface);
         true
     }
 
-    fn on_fullscreen_enter(&mut self) {
-        self.fullscreen_count += 1;
-        if self.fullscreen_count == 1 && !self.paused_for_fs {
-            self.paused_for_fs = true;
+    /// Destroy the layer surface of output `name`.  The caller must drop any
+    /// renderer presenting onto it first.
+    pub fn destroy_layer_surface(&mut self, name: u32) {
+        let Some(output) = self.outputs.get_mut(&name) else {
+            return;
+        };
+        if let Some(viewport) = output.viewport.take() {
+            viewport.destroy();
         }
+        if let Some(fractional_scale) = output.fractional_scale.take() {
+            fractional_scale.destroy();
+        }
+        output.preferred_scale = None;
+        if let Some(layer_surface) = output.layer_surface.take() {
+            layer_surface.destroy();
+        }
+        if let Some(surface) = output.surface.take() {
+            surface.destroy();
+        }
+        output.configured = false;
+        output.frame_requested = None;
+        output.last_presented = None;
+        output.refresh = None;
     }
 
-    fn on_fullscreen_leave(&mut self) {
-        if self.fullscreen_count > 0 {
-            self.fullscreen_count -= 1;
+    /// Forget output `name` after its global was removed.  The caller must
+    /// have torn down its layer surface already.
+    pub fn remove_output(&mut self, name: u32) {
+        let Some(output) = self.outputs.remove(&name) else {
+            return;
+        };
+        if let Some(xdg_output) = output.xdg_output {
+            xdg_output.destroy();
         }
-        if self.fullscreen_count == 0 && self.paused_for_fs {
-            self.paused_for_fs = false;
+        if let Some(power) = output.power {
+            power.destroy();
+        }
+        if output.wl_output.version() >= 3 {
+            output.wl_output.release();
         }
     }
 
-    fn on_window_active_enter(&mut self) {
-        self.active_or_maximized_count += 1;
-        if self.active_or_maximized_count == 1 {
-            if !self.muted_for_windows {
-                self.muted_for_windows = true;
-            }
-            if !self.paused_for_windows {
-                self.paused_for_windows = true;
-            }
-        }
+    /// All toplevel windows, on any output.
+    pub fn windows(&self) -> impl Iterator<Item = &Toplevel> {
+        self.toplevels.values()
     }
 
-    fn on_window_active_leave(&mut self) {
-        if self.active_or_maximized_count > 0 {
-            self.active_or_maximized_count -= 1;
+    /// Get told when the seat has been idle for `timeout`, replacing the
+    /// previous timeout; `None` stops watching.  Idle inhibitors, such as a
+    /// video player's, are honoured.
+    pub fn watch_idle(&mut self, timeout: Option<Duration>, qh: &QueueHandle<State>) {
+        if self.idle_notification.as_ref().map(|(_, t)| *t) == timeout {
+            return;
         }
-        if self.active_or_maximized_count == 0 {
-            if self.muted_for_windows {
-                self.muted_for_windows = false;
-            }
-            if self.paused_for_windows {
-                self.paused_for_windows = false;
-            }
+        if let Some((notification, _)) = self.idle_notification.take() {
+            notification.destroy();
+        }
+        self.idle = false;
+        if let (Some(timeout), Some(notifier), Some(seat)) =
+            (timeout, &self.idle_notifier, &self.seat)
+        {
+            let millis = timeout.as_millis().min(u32::MAX as u128) as u32;
+            let notification = notifier.get_idle_notification(millis, seat, qh, ());
+            self.idle_notification = Some((notification, timeout));
+        }
+    }
+
+    /// Recompute the per-output window counts after a toplevel changed.
+    fn recount_windows(&mut self) {
+        for (name, output) in self.outputs.iter_mut() {
+            let on_output = |t: &&Toplevel| t.is_on(*name);
+            let windows = self.toplevels.values();
+            output.fullscreen_count = windows
+                .clone()
+                .filter(on_output)
+                .filter(|t| t.fullscreen)
+                .count() as u32;
+            output.active_count = windows.filter(on_output).filter(|t| t.active).count() as u32;
         }
     }
 }
 
 impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
     fn event(
-        _state: &mut Self,
-        _registry: &wl_registry::WlRegistry,
-        _event: wl_registry::Event,
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
         _data: &GlobalListContents,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
     ) {
         // GlobalListContents is already maintained by the wayland-client
-        // infrastructure before this callback fires.  Nothing to do.
+        // infrastructure before this callback fires.  We only care about
+        // monitors being plugged in or out at runtime; the main loop builds
+        // and tears down the wallpapers.
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } if interface == wl_output::WlOutput::interface().name => {
+                state.add_output(registry, name, version, qh);
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                if let Some(output) = state.outputs.get_mut(&name) {
+                    output.removed = true;
+                }
+            }
+            _ => {}
+        }
     }
 }
 
@@ -195,6 +525,175 @@ impl Dispatch<wl_surface::WlSurface, ()> for State {
     }
 }
 
+impl Dispatch<wl_callback::WlCallback, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event
+            && let Some(output) = state.outputs.get_mut(name)
+        {
+            output.frame_requested = None;
+        }
+    }
+}
+
+impl Dispatch<WpPresentation, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &WpPresentation,
+        event: wp_presentation::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wp_presentation::Event::ClockId { clk_id } = event {
+            state.presentation_clock = clk_id as libc::clockid_t;
+        }
+    }
+}
+
+impl Dispatch<WpPresentationFeedback, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &WpPresentationFeedback,
+        event: wp_presentation_feedback::Event,
+        name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // Discarded frames were replaced before reaching the screen and
+        // tell nothing about its timing.
+        if let wp_presentation_feedback::Event::Presented {
+            tv_sec_hi,
+            tv_sec_lo,
+            tv_nsec,
+            refresh,
+            ..
+        } = event
+            && let Some(output) = state.outputs.get_mut(name)
+        {
+            let secs = (u64::from(tv_sec_hi) << 32) | u64::from(tv_sec_lo);
+            output.last_presented = Some(Duration::new(secs, tv_nsec));
+            output.refresh = (refresh > 0).then(|| Duration::from_nanos(refresh.into()));
+        }
+    }
+}
+
+impl Dispatch<ZxdgOutputManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZxdgOutputManagerV1,
+        _: zxdg_output_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WpFractionalScaleManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WpFractionalScaleManagerV1,
+        _: wp_fractional_scale_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WpViewporter, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WpViewporter,
+        _: wp_viewporter::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WpViewport, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WpViewport,
+        _: wp_viewport::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(output) = state.outputs.get_mut(name) else {
+            return;
+        };
+        match event {
+            wl_output::Event::Geometry {
+                transform: WEnum::Value(transform),
+                ..
+            } => output.transform = transform,
+            wl_output::Event::Scale { factor } => output.scale = factor.max(1),
+            wl_output::Event::Name { name } => output.name = Some(name),
+            wl_output::Event::Description { description } => {
+                output.description = Some(description);
+            }
+            // xdg-output v3 sends its name before wl_output.done; once we
+            // have it the xdg_output object has nothing more to tell us.
+            wl_output::Event::Done => {
+                output.ready = true;
+                if output.name.is_some()
+                    && let Some(xdg_output) = output.xdg_output.take()
+                {
+                    xdg_output.destroy();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZxdgOutputV1, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(output) = state.outputs.get_mut(name) else {
+            return;
+        };
+        match event {
+            // wl_output v4 names take precedence if both are present.
+            zxdg_output_v1::Event::Name { name } => {
+                output.name.get_or_insert(name);
+            }
+            zxdg_output_v1::Event::Description { description } => {
+                output.description.get_or_insert(description);
+            }
+            _ => {}
+        }
+    }
+}
+
 impl Dispatch<ZwlrLayerShellV1, ()> for State {
     fn event(
         _: &mut Self,
@@ -228,15 +727,18 @@ impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
     ]);
 }
 
-impl Dispatch<ZwlrLayerSurfaceV1, ()> for State {
+impl Dispatch<ZwlrLayerSurfaceV1, u32> for State {
     fn event(
         state: &mut Self,
         layer_surface: &ZwlrLayerSurfaceV1,
         event: zwlr_layer_surface_v1::Event,
-        _: &(),
+        name: &u32,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
     ) {
+        let Some(output) = state.outputs.get_mut(name) else {
+            return;
+        };
         match event {
             zwlr_layer_surface_v1::Event::Configure {
                 serial,
@@ -248,26 +750,45 @@ impl Dispatch<ZwlrLayerSurfaceV1, ()> for State {
                 let w = if width == 0 { 1920 } else { width as i32 };
                 let h = if height == 0 { 1080 } else { height as i32 };
 
-                if w != state.buf_w || h != state.buf_h {
-                    state.buf_w = w;
-                    state.buf_h = h;
+                if w != output.width || h != output.height {
+                    output.width = w;
+                    output.height = h;
                 }
 
-                if let Some(surf) = &state.surface {
+                if let Some(surf) = &output.surface {
                     surf.commit();
                 }
 
-                state.configured = true;
+                output.configured = true;
             }
+            // Sent e.g. when the output goes away.  Not fatal: other outputs
+            // keep playing and hotplugged ones get new surfaces.
             zwlr_layer_surface_v1::Event::Closed => {
                 eprintln!("q6w: layer surface closed by compositor");
-                state.running = false;
+                output.closed = true;
             }
             _ => {}
         }
     }
 }
 
+impl Dispatch<WpFractionalScaleV1, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &WpFractionalScaleV1,
+        event: wp_fractional_scale_v1::Event,
+        name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wp_fractional_scale_v1::Event::PreferredScale { scale } = event
+            && let Some(output) = state.outputs.get_mut(name)
+        {
+            output.preferred_scale = Some(scale);
+        }
+    }
+}
+
 impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
     fn event(
         state: &mut Self,
@@ -277,55 +798,156 @@ impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
     ) {
-        use wayland_client::Proxy;
         let id = handle.id();
+        // Registry name of one of our outputs.
+        let output_name = |state: &Self, output: &wl_output::WlOutput| {
+            state
+                .outputs
+                .iter()
+                .find(|(_, o)| &o.wl_output == output)
+                .map(|(&name, _)| name)
+        };
 
         match event {
             zwlr_foreign_toplevel_handle_v1::Event::State { state: raw } => {
                 // States are packed as little-endian u32 values in a byte array
-                let is_fullscreen = raw.chunks_exact(4).any(|c| {
-                    u32::from_ne_bytes(c.try_into().unwrap())
-                        == zwlr_foreign_toplevel_handle_v1::State::Fullscreen as u32
-                });
-                let is_activated = raw.chunks_exact(4).any(|c| {
-                    u32::from_ne_bytes(c.try_into().unwrap())
-                        == zwlr_foreign_toplevel_handle_v1::State::Activated as u32
-                });
-                let is_maximized = raw.chunks_exact(4).any(|c| {
-                    u32::from_ne_bytes(c.try_into().unwrap())
-                        == zwlr_foreign_toplevel_handle_v1::State::Maximized as u32
-                });
-                let is_fs_active = is_fullscreen && is_activated;
-                let is_active_or_max = is_activated || is_maximized;
-
-                let (was_fs_active, was_active_or_max) = state
-                    .toplevel_states
-                    .insert(id, (is_fs_active, is_active_or_max))
-                    .unwrap_or((false, false));
-
-                if is_fs_active && !was_fs_active {
-                    state.on_fullscreen_enter();
-                }
-                if !is_fs_active && was_fs_active {
-                    state.on_fullscreen_leave();
-                }
-
-                if is_active_or_max && !was_active_or_max {
-                    state.on_window_active_enter();
+                let has = |wanted: zwlr_foreign_toplevel_handle_v1::State| {
+                    raw.chunks_exact(4)
+                        .any(|c| u32::from_ne_bytes(c.try_into().unwrap()) == wanted as u32)
+                };
+                let is_fullscreen = has(zwlr_foreign_toplevel_handle_v1::State::Fullscreen);
+                let is_activated = has(zwlr_foreign_toplevel_handle_v1::State::Activated);
+                let is_maximized = has(zwlr_foreign_toplevel_handle_v1::State::Maximized);
+                let is_minimized = has(zwlr_foreign_toplevel_handle_v1::State::Minimized);
+
+                let toplevel = state.toplevels.entry(id).or_default();
+                toplevel.fullscreen = is_fullscreen && !is_minimized;
+                toplevel.active = is_activated || is_maximized;
+                state.recount_windows();
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                state.toplevels.entry(id).or_default().app_id = app_id;
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => {
+                state.toplevels.entry(id).or_default().title = title;
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::OutputEnter { output } => {
+                // Even on an output that isn't ours, the window is now known
+                // not to be everywhere.
+                let name = output_name(state, &output);
+                let outputs = state
+                    .toplevels
+                    .entry(id)
+                    .or_default()
+                    .outputs
+                    .get_or_insert_default();
+                if let Some(name) = name
+                    && !outputs.contains(&name)
+                {
+                    outputs.push(name);
                 }
-                if !is_active_or_max && was_active_or_max {
-                    state.on_window_active_leave();
+                state.recount_windows();
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::OutputLeave { output } => {
+                if let Some(name) = output_name(state, &output)
+                    && let Some(outputs) = state
+                        .toplevels
+                        .get_mut(&id)
+                        .and_then(|t| t.outputs.as_mut())
+                {
+                    outputs.retain(|&o| o != name);
+                    state.recount_windows();
                 }
             }
             zwlr_foreign_toplevel_handle_v1::Event::Closed => {
-                if let Some((was_fs, was_active)) = state.toplevel_states.remove(&id) {
-                    if was_fs {
-                        state.on_fullscreen_leave();
-                    }
-                    if was_active {
-                        state.on_window_active_leave();
-                    }
+                if state.toplevels.remove(&id).is_some() {
+                    state.recount_windows();
                 }
+                handle.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+// ─── Dispatch: idle notification ─────────────────────────────────────────────
+
+impl Dispatch<wl_seat::WlSeat, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_seat::WlSeat,
+        _: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotifierV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ExtIdleNotifierV1,
+        _: ext_idle_notifier_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => state.idle = true,
+            ext_idle_notification_v1::Event::Resumed => state.idle = false,
+            _ => {}
+        }
+    }
+}
+
+// ─── Dispatch: output power ──────────────────────────────────────────────────
+
+impl Dispatch<ZwlrOutputPowerManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrOutputPowerManagerV1,
+        _: zwlr_output_power_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerV1, u32> for State {
+    fn event(
+        state: &mut Self,
+        power: &ZwlrOutputPowerV1,
+        event: zwlr_output_power_v1::Event,
+        name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(output) = state.outputs.get_mut(name) else {
+            return;
+        };
+        match event {
+            zwlr_output_power_v1::Event::Mode { mode } => {
+                output.powered_off = mode == WEnum::Value(zwlr_output_power_v1::Mode::Off);
+            }
+            // Unsupported by the output, or it went away: assume it is on.
+            zwlr_output_power_v1::Event::Failed => {
+                output.powered_off = false;
+                output.power = None;
+                power.destroy();
             }
             _ => {}
         }
@@ -0,0 +1,708 @@
+// SPDX-License-Identifier: AGPL-3.0-only
+// Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
+
+//! Configuration file.
+//!
+//! `$XDG_CONFIG_HOME/q6w/config.toml` (or `--config FILE`) holds the same
+//! settings as the command line, spelled like the long flags without the
+//! dashes:
+//!
+//! ```toml
+//! playlist = "~/Videos/wallpapers"
+//! interval = "10m"
+//! pause-on-window = true
+//! profile = "desk"            # default profile, --profile overrides
+//!
+//! [output.DP-1]               # per monitor: file, scale-mode, filter,
+//! file = "forest.mp4"         # letterbox-color and fps
+//! scale-mode = "fit"
+//!
+//! [profiles.battery]          # any of the above, layered on top
+//! fps = 24
+//!
+//! [[rule]]                    # window rules, see `rules`
+//! app-id = "steam_app_*"
+//! pause = true
+//!
+//! [[power]]                   # battery policy, see `power`
+//! below = 20
+//! pause = true
+//! ```
+//!
+//! Top-level keys are turned into command-line arguments and parsed by clap
+//! in front of the real command line, so they are validated exactly like
+//! flags and flags given on the command line win; a switch set in the file
+//! can't be turned off there, only by a profile.  Relative paths are
+//! resolved against the file's directory and `~/` expands to `$HOME`.
+//!
+//! The file's directory is watched with inotify so edits apply while q6w
+//! runs.
+
+use std::collections::BTreeMap;
+use std::ffi::{CString, OsString};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use clap::parser::ValueSource;
+use toml::{Table, Value};
+
+use crate::gpu_renderer::{Filter, ScaleMode};
+use crate::power::{Action, Policies, Policy};
+use crate::rules::{Effect, Rule, Rules};
+
+/// Flags that only make sense on the command line.
+const CLI_ONLY: &[&str] = &["config", "profile", "license", "help", "version", "output"];
+
+/// Settings that only apply to a playlist.
+const PLAYLIST_ONLY: &[&str] = &["shuffle", "interval", "advance-on-eos"];
+
+/// Overrides from an `[output.NAME]` section.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OutputConfig {
+    pub scale_mode: Option<ScaleMode>,
+    pub filter: Option<Filter>,
+    pub letterbox_color: Option<[f32; 3]>,
+    pub fps: Option<i32>,
+}
+
+pub struct Config {
+    /// Top-level settings and per-output files as command-line arguments,
+    /// minus those the real command line already sets.
+    pub args: Vec<OsString>,
+    /// `[output.NAME]` overrides by connector name.
+    pub outputs: BTreeMap<String, OutputConfig>,
+    /// `[[rule]]` sections, in file order.
+    pub rules: Rules,
+    /// `[[power]]` sections.
+    pub power: Policies,
+}
+
+/// `$XDG_CONFIG_HOME/q6w/config.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("q6w").join("config.toml"))
+}
+
+impl Config {
+    /// Read `path` and apply `profile`, or the file's `profile` key.
+    ///
+    /// `cmd` describes the flags the keys map to; `cli` tells which of them
+    /// the command line already set.
+    pub fn load(
+        path: &Path,
+        profile: Option<&str>,
+        cmd: &clap::Command,
+        cli: &clap::ArgMatches,
+    ) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut table: Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        let base = path.parent().unwrap_or(Path::new("."));
+
+        let profiles = table.remove("profiles");
+        let default_profile = match table.remove("profile") {
+            Some(Value::String(name)) => Some(name),
+            None => None,
+            Some(_) => return Err("`profile` must be a string".into()),
+        };
+        let profile = profile.map(str::to_owned).or(default_profile);
+        if let Some(name) = profile {
+            let overlay = profiles
+                .as_ref()
+                .and_then(|p| p.get(&name))
+                .and_then(Value::as_table)
+                .ok_or_else(|| format!("no [profiles.{name}] section"))?;
+            merge(&mut table, overlay);
+        }
+        let outputs = table.remove("output");
+        let rules = table.remove("rule");
+        let power = table.remove("power");
+
+        let on_cli = |id: &str| cli.value_source(id) == Some(ValueSource::CommandLine);
+        // A video source on the command line replaces the file's.
+        let cli_video = on_cli("file") || on_cli("playlist");
+        let file_playlist = table.contains_key("playlist");
+
+        let mut config = Config {
+            args: Vec::new(),
+            outputs: BTreeMap::new(),
+            rules: Rules::default(),
+            power: Policies::default(),
+        };
+        for (key, value) in &table {
+            let arg = cmd
+                .get_arguments()
+                .find(|a| a.get_long() == Some(key.as_str()))
+                .filter(|_| !CLI_ONLY.contains(&key.as_str()))
+                .ok_or_else(|| format!("unknown setting `{key}`"))?;
+            let is_path = key == "file" || key == "playlist";
+            if on_cli(arg.get_id().as_str()) || (is_path && cli_video) {
+                continue;
+            }
+            if PLAYLIST_ONLY.contains(&key.as_str()) {
+                // They went with the file's playlist that --file replaces.
+                if on_cli("file") {
+                    continue;
+                }
+                if !file_playlist && !on_cli("playlist") {
+                    return Err(format!("`{key}` needs `playlist`"));
+                }
+            }
+
+            if !arg.get_action().takes_values() {
+                match value {
+                    Value::Boolean(true) => config.args.push(format!("--{key}").into()),
+                    Value::Boolean(false) => {}
+                    _ => return Err(format!("`{key}` must be true or false")),
+                }
+                continue;
+            }
+            let mut flag = OsString::from(format!("--{key}="));
+            match value {
+                Value::String(s) if is_path => flag.push(expand(base, s)),
+                Value::String(s) => flag.push(s),
+                Value::Integer(n) => flag.push(n.to_string()),
+                Value::Float(f) => flag.push(f.to_string()),
+                _ => return Err(format!("`{key}` must be a string or a number")),
+            }
+            config.args.push(flag);
+        }
+
+        if let Some(outputs) = outputs {
+            let outputs = outputs
+                .as_table()
+                .ok_or("`output` must be made of [output.NAME] sections")?;
+            for (name, section) in outputs {
+                let out = config.read_output(base, name, section)?;
+                config.outputs.insert(name.clone(), out);
+            }
+        }
+        if let Some(rules) = rules {
+            let rules = rules
+                .as_array()
+                .ok_or("`rule` must be made of [[rule]] sections")?;
+            for (i, rule) in rules.iter().enumerate() {
+                config.rules.0.push(read_rule(i + 1, rule)?);
+            }
+        }
+        if let Some(power) = power {
+            let power = power
+                .as_array()
+                .ok_or("`power` must be made of [[power]] sections")?;
+            for (i, policy) in power.iter().enumerate() {
+                config.power.0.push(read_power(i + 1, policy)?);
+            }
+        }
+        Ok(config)
+    }
+
+    fn read_output(
+        &mut self,
+        base: &Path,
+        name: &str,
+        section: &Value,
+    ) -> Result<OutputConfig, String> {
+        let section = section
+            .as_table()
+            .ok_or_else(|| format!("[output.{name}] must be a section"))?;
+        let mut out = OutputConfig::default();
+        for (key, value) in section {
+            let text = || {
+                value
+                    .as_str()
+                    .ok_or_else(|| format!("[output.{name}] `{key}` must be a string"))
+            };
+            let invalid = |e: String| format!("[output.{name}] `{key}`: {e}");
+            match key.as_str() {
+                // Goes through --output so the command line can override it.
+                "file" => {
+                    let mut flag = OsString::from(format!("--output={name}="));
+                    flag.push(expand(base, text()?));
+                    self.args.push(flag);
+                }
+                "scale-mode" => {
+                    out.scale_mode = Some(ScaleMode::from_str(text()?, true).map_err(invalid)?)
+                }
+                "filter" => out.filter = Some(Filter::from_str(text()?, true).map_err(invalid)?),
+                "letterbox-color" => {
+                    out.letterbox_color = Some(crate::parse_color(text()?).map_err(invalid)?)
+                }
+                "fps" => {
+                    let fps = value
+                        .as_integer()
+                        .and_then(|n| i32::try_from(n).ok())
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| invalid("expected a positive whole number".into()))?;
+                    out.fps = Some(fps);
+                }
+                _ => return Err(format!("[output.{name}]: unknown setting `{key}`")),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Parse the `n`th `[[rule]]` section.
+fn read_rule(n: usize, section: &Value) -> Result<Rule, String> {
+    let section = section
+        .as_table()
+        .ok_or_else(|| format!("rule {n} must be a [[rule]] section"))?;
+    let mut rule = Rule {
+        app_id: None,
+        title: None,
+        effect: Effect::default(),
+    };
+    for (key, value) in section {
+        let invalid = |expected: &str| format!("rule {n}: `{key}` must be {expected}");
+        let text = || {
+            value
+                .as_str()
+                .map(str::to_owned)
+                .ok_or_else(|| invalid("a string"))
+        };
+        let flag = || value.as_bool().ok_or_else(|| invalid("true or false"));
+        match key.as_str() {
+            "app-id" => rule.app_id = Some(text()?),
+            "title" => rule.title = Some(text()?),
+            "pause" => rule.effect.pause = flag()?,
+            "mute" => rule.effect.mute = flag()?,
+            "dim" => {
+                let dim = value
+                    .as_float()
+                    .or_else(|| value.as_integer().map(|n| n as f64))
+                    .filter(|d| (0.0..=1.0).contains(d))
+                    .ok_or_else(|| invalid("a number from 0.0 to 1.0"))?;
+                rule.effect.dim = Some(dim as f32);
+            }
+            "fps" => {
+                let fps = value
+                    .as_integer()
+                    .and_then(|n| i32::try_from(n).ok())
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| invalid("a positive whole number"))?;
+                rule.effect.fps = Some(fps);
+            }
+            _ => return Err(format!("rule {n}: unknown setting `{key}`")),
+        }
+    }
+    if rule.app_id.is_none() && rule.title.is_none() {
+        return Err(format!("rule {n}: needs `app-id` or `title`"));
+    }
+    Ok(rule)
+}
+
+/// Parse the `n`th `[[power]]` section.
+fn read_power(n: usize, section: &Value) -> Result<Policy, String> {
+    let section = section
+        .as_table()
+        .ok_or_else(|| format!("power policy {n} must be a [[power]] section"))?;
+    let mut policy = Policy {
+        below: None,
+        action: Action::default(),
+    };
+    for (key, value) in section {
+        let invalid = |expected: &str| format!("power policy {n}: `{key}` must be {expected}");
+        let flag = || value.as_bool().ok_or_else(|| invalid("true or false"));
+        match key.as_str() {
+            "below" => {
+                let below = value
+                    .as_integer()
+                    .and_then(|n| u8::try_from(n).ok())
+                    .filter(|n| (1..=100).contains(n))
+                    .ok_or_else(|| invalid("a percentage from 1 to 100"))?;
+                policy.below = Some(below);
+            }
+            "pause" => policy.action.pause = flag()?,
+            "still" => policy.action.still = flag()?,
+            "fps" => {
+                let fps = value
+                    .as_integer()
+                    .and_then(|n| i32::try_from(n).ok())
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| invalid("a positive whole number"))?;
+                policy.action.fps = Some(fps);
+            }
+            _ => return Err(format!("power policy {n}: unknown setting `{key}`")),
+        }
+    }
+    Ok(policy)
+}
+
+/// Overlay `src` onto `dst`, merging sections key by key.
+fn merge(dst: &mut Table, src: &Table) {
+    for (key, value) in src {
+        match (dst.get_mut(key), value) {
+            (Some(Value::Table(d)), Value::Table(s)) => merge(d, s),
+            _ => {
+                dst.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Resolve a path from the file: `~/` is `$HOME`, relative paths start at
+/// the file's directory.
+fn expand(base: &Path, path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match (
+        path.strip_prefix("~/").or((path == "~").then_some("")),
+        home,
+    ) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => base.join(path),
+    }
+}
+
+/// Watches the config file for changes.
+///
+/// The directory is watched rather than the file, because editors usually
+/// save by writing a new file and renaming it over the old one.
+pub struct Watcher {
+    fd: OwnedFd,
+    file_name: OsString,
+}
+
+impl Watcher {
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| format!("{}: not a file", path.display()))?
+            .to_owned();
+        let dir_c = CString::new(dir.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let wd = unsafe {
+            libc::inotify_add_watch(
+                fd.as_raw_fd(),
+                dir_c.as_ptr(),
+                libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO,
+            )
+        };
+        if wd < 0 {
+            let e = std::io::Error::last_os_error();
+            return Err(format!("{}: {e}", dir.display()));
+        }
+        Ok(Watcher { fd, file_name })
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    /// Drain pending events; `true` if the config file was written or
+    /// replaced since the last call.
+    pub fn changed(&self) -> bool {
+        const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+        let mut buf = [0u8; 4096];
+        let mut changed = false;
+        loop {
+            let n = unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 {
+                return changed;
+            }
+            let n = n as usize;
+            let mut offset = 0;
+            while offset + HEADER <= n {
+                let event: libc::inotify_event =
+                    unsafe { std::ptr::read_unaligned(buf.as_ptr().add(offset).cast()) };
+                let name_end = (offset + HEADER + event.len as usize).min(n);
+                let name = &buf[offset + HEADER..name_end];
+                // The name is NUL-padded.
+                let name = name.split(|&b| b == 0).next().unwrap_or_default();
+                if name == self.file_name.as_bytes() {
+                    changed = true;
+                }
+                offset = name_end;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+    use clap::{CommandFactory, Parser};
+
+    /// A config file in a directory of its own, removed on drop.
+    struct File(PathBuf);
+
+    impl File {
+        fn new(name: &str, text: &str) -> File {
+            let dir =
+                std::env::temp_dir().join(format!("q6w-config-{}-{name}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("config.toml");
+            std::fs::write(&path, text).unwrap();
+            File(path)
+        }
+
+        fn dir(&self) -> &Path {
+            self.0.parent().unwrap()
+        }
+
+        /// Load the file with `cli` as the command line.
+        fn load(&self, profile: Option<&str>, cli: &[&str]) -> Result<Config, String> {
+            let cli = Args::command()
+                .try_get_matches_from(std::iter::once("q6w").chain(cli.iter().copied()))
+                .unwrap();
+            Config::load(&self.0, profile, &Args::command(), &cli)
+        }
+    }
+
+    impl Drop for File {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(self.dir());
+        }
+    }
+
+    fn flag(s: &str) -> OsString {
+        s.into()
+    }
+
+    #[test]
+    fn command_line_wins() {
+        let file = File::new(
+            "precedence",
+            r#"
+            file = "forest.mp4"
+            fps = 24
+            pause-on-window = true
+            mute-on-window = false
+            "#,
+        );
+        let forest = flag(&format!(
+            "--file={}",
+            file.dir().join("forest.mp4").display()
+        ));
+
+        let config = file.load(None, &[]).unwrap();
+        assert_eq!(
+            config.args,
+            [forest.clone(), flag("--fps=24"), flag("--pause-on-window")]
+        );
+
+        let cli = ["--fps", "30"];
+        let config = file.load(None, &cli).unwrap();
+        assert_eq!(config.args, [forest, flag("--pause-on-window")]);
+        let argv = std::iter::once(flag("q6w"))
+            .chain(config.args)
+            .chain(cli.map(flag));
+        let args = Args::try_parse_from(argv).unwrap();
+        assert_eq!(args.fps, Some(30));
+        assert!(args.pause_on_window);
+
+        // Any video source on the command line replaces the file's.
+        let config = file.load(None, &["--playlist", "/videos"]).unwrap();
+        assert_eq!(config.args, [flag("--fps=24"), flag("--pause-on-window")]);
+    }
+
+    #[test]
+    fn playlist_settings() {
+        let file = File::new(
+            "playlist",
+            r#"
+            playlist = "videos"
+            shuffle = true
+            interval = "10m"
+            "#,
+        );
+        let videos = flag(&format!(
+            "--playlist={}",
+            file.dir().join("videos").display()
+        ));
+        let config = file.load(None, &[]).unwrap();
+        assert_eq!(
+            config.args,
+            [flag("--interval=10m"), videos, flag("--shuffle")]
+        );
+        // Another playlist keeps them, a single file drops them.
+        let config = file.load(None, &["--playlist", "/videos"]).unwrap();
+        assert_eq!(config.args, [flag("--interval=10m"), flag("--shuffle")]);
+        let config = file.load(None, &["--file", "/a.mp4"]).unwrap();
+        assert!(config.args.is_empty());
+
+        // Without any playlist they'd do nothing.
+        let file = File::new("no-playlist", "shuffle = true");
+        let config = file.load(None, &["--playlist", "/videos"]).unwrap();
+        assert_eq!(config.args, [flag("--shuffle")]);
+        assert!(Args::try_parse_from(["q6w", "--shuffle"]).is_err());
+        // `requires` alone lets a conflicting --file through.
+        assert!(Args::try_parse_from(["q6w", "--file", "a.mp4", "--shuffle"]).is_err());
+        assert!(Args::try_parse_from(["q6w", "--playlist", "/videos", "--shuffle"]).is_ok());
+    }
+
+    #[test]
+    fn profiles() {
+        let file = File::new(
+            "profiles",
+            r#"
+            profile = "desk"
+            fps = 60
+
+            [output.DP-1]
+            scale-mode = "fit"
+
+            [profiles.desk]
+            fps = 30
+
+            [profiles.battery]
+            fps = 12
+            [profiles.battery.output.DP-1]
+            filter = "nearest"
+            "#,
+        );
+        let config = file.load(None, &[]).unwrap();
+        assert_eq!(config.args, [flag("--fps=30")]);
+        assert_eq!(config.outputs["DP-1"].filter, None);
+
+        // --profile replaces the file's default; sections merge key by key.
+        let config = file.load(Some("battery"), &[]).unwrap();
+        assert_eq!(config.args, [flag("--fps=12")]);
+        assert_eq!(
+            config.outputs["DP-1"],
+            OutputConfig {
+                scale_mode: Some(ScaleMode::Fit),
+                filter: Some(Filter::Nearest),
+                ..OutputConfig::default()
+            }
+        );
+
+        let err = file.load(Some("travel"), &[]).err().unwrap();
+        assert_eq!(err, "no [profiles.travel] section");
+
+        // The default is checked even when --profile replaces it.
+        let file = File::new(
+            "bad-profile",
+            "profile = 3
+[profiles.desk]
+fps = 30",
+        );
+        let err = file.load(Some("desk"), &[]).err();
+        assert_eq!(err.as_deref(), Some("`profile` must be a string"));
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("bogus = 1", "unknown setting `bogus`"),
+            ("shuffle = true", "`shuffle` needs `playlist`"),
+            (
+                "file = \"a.mp4\"\ninterval = \"10m\"",
+                "`interval` needs `playlist`",
+            ),
+            // Command-line-only flags aren't settings.
+            ("license = true", "unknown setting `license`"),
+            (
+                "output = 1",
+                "`output` must be made of [output.NAME] sections",
+            ),
+            ("audio = 1", "`audio` must be true or false"),
+            ("fps = [30]", "`fps` must be a string or a number"),
+            ("profile = 1", "`profile` must be a string"),
+            (
+                "[output.DP-1]\nbogus = 1",
+                "[output.DP-1]: unknown setting `bogus`",
+            ),
+            (
+                "[output.DP-1]\nfps = 0",
+                "[output.DP-1] `fps`: expected a positive whole number",
+            ),
+            (
+                "[output.DP-1]\nscale-mode = 1",
+                "[output.DP-1] `scale-mode` must be a string",
+            ),
+            (
+                "[[rule]]\napp-id = \"mpv\"\nbogus = 1",
+                "rule 1: unknown setting `bogus`",
+            ),
+            (
+                "[[rule]]\npause = true",
+                "rule 1: needs `app-id` or `title`",
+            ),
+            (
+                "[[power]]\nbelow = 0",
+                "power policy 1: `below` must be a percentage from 1 to 100",
+            ),
+            (
+                "[[power]]\nbogus = 1",
+                "power policy 1: unknown setting `bogus`",
+            ),
+        ];
+        for (i, (text, expected)) in cases.into_iter().enumerate() {
+            let file = File::new(&format!("error-{i}"), text);
+            assert_eq!(
+                file.load(None, &[]).err().as_deref(),
+                Some(expected),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn paths() {
+        let base = Path::new("/etc/q6w");
+        assert_eq!(expand(base, "a.mp4"), base.join("a.mp4"));
+        assert_eq!(expand(base, "/srv/a.mp4"), Path::new("/srv/a.mp4"));
+        // Only the current user's home expands.
+        assert_eq!(expand(base, "~bob/a.mp4"), base.join("~bob/a.mp4"));
+        if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+            assert_eq!(expand(base, "~/a.mp4"), home.join("a.mp4"));
+            assert_eq!(expand(base, "~"), home);
+        }
+    }
+
+    #[test]
+    fn output_sections() {
+        let file = File::new(
+            "outputs",
+            r##"
+            [output.DP-1]
+            file = "forest.mp4"
+            scale-mode = "fit"
+            letterbox-color = "#ff0000"
+            fps = 30
+
+            [output.HDMI-A-1]
+            filter = "nearest"
+            "##,
+        );
+        let config = file.load(None, &[]).unwrap();
+        let forest = file.dir().join("forest.mp4");
+        assert_eq!(
+            config.args,
+            [flag(&format!("--output=DP-1={}", forest.display()))]
+        );
+        assert_eq!(
+            config.outputs["DP-1"],
+            OutputConfig {
+                scale_mode: Some(ScaleMode::Fit),
+                filter: None,
+                letterbox_color: Some([1.0, 0.0, 0.0]),
+                fps: Some(30),
+            }
+        );
+        assert_eq!(
+            config.outputs["HDMI-A-1"],
+            OutputConfig {
+                filter: Some(Filter::Nearest),
+                ..OutputConfig::default()
+            }
+        );
+
+        let args = Args::try_parse_from(std::iter::once(flag("q6w")).chain(config.args)).unwrap();
+        assert_eq!(args.output, [("DP-1".to_owned(), forest)]);
+    }
+}
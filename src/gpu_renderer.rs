@@ -20,12 +20,33 @@
 //! the GPU sees `.r = B, .g = G, .b = R, .a = A` in memory order.
 //! The fragment shader corrects this with a single `vec4(c.b, c.g, c.r, c.a)`
 //! swizzle — no extra copy.
+//!
+//! # Buffer transform
+//! On rotated or flipped outputs the surface's buffer transform is set to the
+//! output transform, so the swapchain is in the panel's native orientation.
+//! The fragment shader maps each buffer pixel back to the upright surface
+//! position before sampling the video.
+//!
+//! # Scale modes
+//! The frame texture rarely matches the surface size.  How it is placed
+//! (fill, fit, stretch, center, tile) is a UV scale + offset computed on the
+//! CPU whenever the frame or surface size changes; pixels outside the video
+//! get the letterbox colour.
+//!
+//! # Transitions
+//! When the video changes, the last frame of the outgoing video is kept in a
+//! second texture and the shader blends it with the incoming video (fade,
+//! wipe or dissolve) while `progress` runs from 0 to 1.  Each texture has its
+//! own UV transform since the two videos need not share a size.
 
 use std::ffi::c_void;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use raw_window_handle::{
     RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
 };
+use wayland_client::protocol::wl_output::Transform;
 
 const SHADER_SRC: &str = r#"
 // Six vertices for two triangles covering NDC space.
@@ -42,45 +63,255 @@ fn vs(@builtin(vertex_index) vi: u32) -> VO {
     return VO(vec4(p, 0.0, 1.0), vec2((p.x + 1.0) * 0.5, (1.0 - p.y) * 0.5));
 }
 
+struct Params {
+    uv_scale: vec2<f32>,
+    uv_offset: vec2<f32>,
+    prev_uv_scale: vec2<f32>,
+    prev_uv_offset: vec2<f32>,
+    letterbox: vec4<f32>,
+    transform: u32,
+    tile: u32,
+    transition: u32,
+    progress: f32,
+    brightness: f32,
+};
+
 @group(0) @binding(0) var tex: texture_2d<f32>;
 @group(0) @binding(1) var smp: sampler;
+@group(0) @binding(2) var<uniform> params: Params;
+@group(0) @binding(3) var prev_tex: texture_2d<f32>;
+
+// Map a buffer UV to the surface UV it ends up at once the compositor undoes
+// the buffer transform.  Cases are wl_output.transform values.
+fn surface_uv(b: vec2<f32>) -> vec2<f32> {
+    switch params.transform {
+        case 1u: { return vec2(b.y, 1.0 - b.x); }       // 90
+        case 2u: { return vec2(1.0 - b.x, 1.0 - b.y); } // 180
+        case 3u: { return vec2(1.0 - b.y, b.x); }       // 270
+        case 4u: { return vec2(1.0 - b.x, b.y); }       // flipped
+        case 5u: { return vec2(1.0 - b.y, 1.0 - b.x); } // flipped-90
+        case 6u: { return vec2(b.x, 1.0 - b.y); }       // flipped-180
+        case 7u: { return vec2(b.y, b.x); }             // flipped-270
+        default: { return b; }
+    }
+}
+
+// Sample one frame texture placed on the surface by `scale`/`offset`.
+fn frame(t: texture_2d<f32>, s: vec2<f32>, scale: vec2<f32>, offset: vec2<f32>) -> vec4<f32> {
+    let uv = s * scale + offset;
+    // Tiling wraps through the sampler's Repeat address mode.
+    let outside = params.tile == 0u && (any(uv < vec2(0.0)) || any(uv > vec2(1.0)));
+    // Texture is Rgba8Unorm but stores BGRA bytes → swap B↔R
+    let c = textureSample(t, smp, uv);
+    return select(vec4(c.b, c.g, c.r, c.a), params.letterbox, outside);
+}
+
+// Cheap per-pixel hash in [0, 1) for the dissolve pattern.
+fn noise(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(floor(p), vec2(12.9898, 78.233))) * 43758.5453);
+}
 
 @fragment
 fn fs(v: VO) -> @location(0) vec4<f32> {
-    // Texture is Rgba8Unorm but stores BGRA bytes → swap B↔R
-    let c = textureSample(tex, smp, v.uv);
-    return vec4(c.b, c.g, c.r, c.a);
+    let s = surface_uv(v.uv);
+    let next = frame(tex, s, params.uv_scale, params.uv_offset);
+    let prev = frame(prev_tex, s, params.prev_uv_scale, params.prev_uv_offset);
+    let t = params.progress;
+    // How much of the incoming video shows at this pixel.  Cases are
+    // `Transition` discriminants; 0 (none) means no transition is running.
+    var k = 1.0;
+    switch params.transition {
+        case 1u: { k = t; }                                        // fade
+        case 2u: { k = 1.0 - smoothstep(t * 1.1 - 0.1, t * 1.1, s.x); } // wipe
+        case 3u: {                                                 // dissolve
+            let n = noise(v.pos.xy * 0.5);
+            k = smoothstep(n - 0.05, n + 0.05, t * 1.1);
+        }
+        default: {}
+    }
+    let c = mix(prev, next, k);
+    return vec4(c.rgb * params.brightness, c.a);
 }
 "#;
 
-pub struct GpuRenderer {
+/// How the video is placed on a surface with a different aspect ratio.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ScaleMode {
+    /// Scale to cover the whole output, cropping the overflow
+    #[default]
+    Fill,
+    /// Scale to fit inside the output, letterboxing the rest
+    Fit,
+    /// Scale to the output size, ignoring the aspect ratio
+    Stretch,
+    /// Show at native size, centred
+    Center,
+    /// Repeat at native size, starting from the centre
+    Tile,
+}
+
+/// Texture filtering used when the video is scaled on the GPU.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Filter {
+    /// Blocky, keeps pixel art crisp
+    Nearest,
+    /// Smooth
+    #[default]
+    Linear,
+}
+
+/// Effect used when switching from one video to the next.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Transition {
+    /// Hard cut
+    None,
+    /// Crossfade
+    #[default]
+    Fade,
+    /// Soft-edged wipe from left to right
+    Wipe,
+    /// Pixels switch over in random order
+    Dissolve,
+}
+
+/// Fragment shader uniforms; layout matches `Params` in `SHADER_SRC`.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    uv_scale: [f32; 2],
+    uv_offset: [f32; 2],
+    prev_uv_scale: [f32; 2],
+    prev_uv_offset: [f32; 2],
+    letterbox: [f32; 4],
+    transform: u32,
+    tile: u32,
+    transition: u32,
+    progress: f32,
+    brightness: f32,
+    _pad: [u32; 3],
+}
+
+/// A frame texture and the size of the frames it holds.
+struct FrameTexture {
+    texture: wgpu::Texture,
+    width: u32,
+    height: u32,
+}
+
+impl FrameTexture {
+    /// The texture is `Rgba8Unorm` — we upload BGRA bytes, the shader swizzles.
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("frame"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        FrameTexture {
+            texture,
+            width,
+            height,
+        }
+    }
+}
+
+/// The wgpu instance, adapter and device every output renders with.  One
+/// device for all monitors: each would otherwise hold its own driver
+/// context, shader and command allocations.
+pub struct Gpu {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
+}
+
+impl Gpu {
+    /// Open a low-power adapter that can present to `surface`, which must
+    /// have been created from `instance`.
+    fn new(instance: wgpu::Instance, surface: &wgpu::Surface) -> anyhow::Result<Self> {
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::LowPower,
+            compatible_surface: Some(surface),
+            force_fallback_adapter: false,
+        }))
+        .ok_or_else(|| anyhow::anyhow!("no wgpu adapter found"))?;
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("q6w"),
+                required_features: wgpu::Features::empty(),
+                required_limits: wgpu::Limits::default(),
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None, // no API call tracing
+        ))?;
+
+        Ok(Gpu {
+            instance,
+            adapter,
+            device,
+            queue,
+        })
+    }
+}
+
+pub struct GpuRenderer {
+    gpu: Arc<Gpu>,
     surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
     pipeline: wgpu::RenderPipeline,
-    texture: wgpu::Texture,
+    bgl: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    scale_mode: ScaleMode,
+    filter: Filter,
+    params: Params,
+    params_buf: wgpu::Buffer,
+    // The frame texture follows the size of the decoded frames, which can
+    // briefly differ from the surface size while the pipeline renegotiates.
+    frame: FrameTexture,
+    // Last frame of the outgoing video during a transition.
+    prev_frame: FrameTexture,
     bind_grp: wgpu::BindGroup,
-    width: u32,
-    height: u32,
+    transition: Transition,
+    transition_start: Instant,
+    transition_len: Duration,
+    /// An unexpected surface error was reported and no frame has been
+    /// presented since, so it isn't reported again every frame.
+    surface_failing: bool,
 }
 
 impl GpuRenderer {
-    /// Create a wgpu renderer that presents onto the given Wayland surface.
+    /// Create a wgpu renderer that presents onto the given Wayland surface,
+    /// on `gpu` if given and able to present there, else on a new device.
     ///
     /// # Safety
     /// Both `display` and `surface` must remain valid for the entire lifetime
     /// of the returned `GpuRenderer`.  They are the raw C pointers to
     /// `wl_display` and `wl_surface` respectively.
     pub unsafe fn new(
+        gpu: Option<Arc<Gpu>>,
         display: *mut c_void,
         surface: *mut c_void,
         width: u32,
         height: u32,
     ) -> anyhow::Result<Self> {
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN | wgpu::Backends::GL,
-            ..Default::default()
-        });
+        let instance = gpu.as_ref().map_or_else(
+            || {
+                wgpu::Instance::new(&wgpu::InstanceDescriptor {
+                    backends: wgpu::Backends::VULKAN | wgpu::Backends::GL,
+                    ..Default::default()
+                })
+            },
+            |gpu| gpu.instance.clone(),
+        );
 
         let wgpu_surface = unsafe {
             let rdh = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(
@@ -95,27 +326,16 @@ impl GpuRenderer {
             })?
         };
 
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::LowPower,
-            compatible_surface: Some(&wgpu_surface),
-            force_fallback_adapter: false,
-        }))
-        .ok_or_else(|| anyhow::anyhow!("no wgpu adapter found"))?;
-
-        let (device, queue) = pollster::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: Some("q6w"),
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
-                memory_hints: wgpu::MemoryHints::Performance,
-            },
-            None, // no API call tracing
-        ))?;
+        let gpu = match gpu {
+            Some(gpu) if gpu.adapter.is_surface_supported(&wgpu_surface) => gpu,
+            _ => Arc::new(Gpu::new(instance, &wgpu_surface)?),
+        };
+        let (device, queue) = (&gpu.device, &gpu.queue);
 
         // Pick surface format — MUST be non-sRGB so video bytes pass through
         // without double gamma correction.  Video content is already sRGB-encoded;
         // the Wayland compositor handles final display gamma.
-        let caps = wgpu_surface.get_capabilities(&adapter);
+        let caps = wgpu_surface.get_capabilities(&gpu.adapter);
         let fmt = caps
             .formats
             .iter()
@@ -123,44 +343,20 @@ impl GpuRenderer {
             .find(|f| !f.is_srgb())
             .unwrap_or(caps.formats[0]);
 
-        wgpu_surface.configure(
-            &device,
-            &wgpu::SurfaceConfiguration {
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                format: fmt,
-                width,
-                height,
-                present_mode: wgpu::PresentMode::Fifo,
-                alpha_mode: wgpu::CompositeAlphaMode::Opaque,
-                view_formats: vec![],
-                desired_maximum_frame_latency: 1,
-            },
-        );
-
-        // Frame texture: Rgba8Unorm — we upload BGRA bytes, shader swizzles
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("frame"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: fmt,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 1,
+        };
+        wgpu_surface.configure(device, &config);
 
-        let tex_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let tex_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
+        let (scale_mode, filter) = (ScaleMode::Stretch, Filter::Linear);
+        let sampler = create_sampler(device, scale_mode, filter);
 
         let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("bgl"),
@@ -181,24 +377,54 @@ impl GpuRenderer {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
-            ],
-        });
-
-        let bind_grp = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("bg"),
-            layout: &bgl,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&tex_view),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&tex_sampler),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
                 },
             ],
         });
 
+        let params = Params {
+            uv_scale: [1.0, 1.0],
+            uv_offset: [0.0, 0.0],
+            prev_uv_scale: [1.0, 1.0],
+            prev_uv_offset: [0.0, 0.0],
+            letterbox: [0.0, 0.0, 0.0, 1.0],
+            transform: Transform::Normal as u32,
+            tile: 0,
+            transition: Transition::None as u32,
+            progress: 1.0,
+            brightness: 1.0,
+            _pad: [0; 3],
+        };
+        let params_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("params"),
+            size: std::mem::size_of::<Params>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&params_buf, 0, bytemuck::bytes_of(&params));
+
+        let frame = FrameTexture::new(device, width, height);
+        let prev_frame = FrameTexture::new(device, 1, 1);
+        let bind_grp = create_bind_group(device, &bgl, &sampler, &params_buf, &frame, &prev_frame);
+
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("blit"),
             source: wgpu::ShaderSource::Wgsl(SHADER_SRC.into()),
@@ -237,27 +463,201 @@ impl GpuRenderer {
         });
 
         Ok(GpuRenderer {
-            device,
-            queue,
+            gpu,
             surface: wgpu_surface,
+            config,
             pipeline,
-            texture,
+            bgl,
+            sampler,
+            scale_mode,
+            filter,
+            params,
+            params_buf,
+            frame,
+            prev_frame,
             bind_grp,
-            width,
-            height,
+            transition: Transition::None,
+            transition_start: Instant::now(),
+            transition_len: Duration::ZERO,
+            surface_failing: false,
         })
     }
 
-    /// Upload `bgra` pixels directly (zero-copy from the GstBuffer map) and
-    /// render them onto the swapchain surface.
+    /// The device this renderer draws with, to share with other outputs.
+    pub fn gpu(&self) -> &Arc<Gpu> {
+        &self.gpu
+    }
+
+    /// Render for a surface whose buffer transform is `transform`.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.params.transform = transform as u32;
+        self.update_layout();
+    }
+
+    /// Choose how the video is placed on the surface.  `letterbox` is the
+    /// RGB colour shown around `fit` and `center`.
+    pub fn set_scaling(&mut self, scale_mode: ScaleMode, filter: Filter, letterbox: [f32; 3]) {
+        if (scale_mode, filter) != (self.scale_mode, self.filter) {
+            self.scale_mode = scale_mode;
+            self.filter = filter;
+            self.sampler = create_sampler(&self.gpu.device, scale_mode, filter);
+            self.rebind();
+        }
+        let [r, g, b] = letterbox;
+        self.params.letterbox = [r, g, b, 1.0];
+        self.update_layout();
+    }
+
+    /// Darken the output by `dim` (0.0 – 1.0).  Shows on the next `render`.
+    pub fn set_dim(&mut self, dim: f32) {
+        self.params.brightness = 1.0 - dim.clamp(0.0, 1.0);
+        self.gpu
+            .queue
+            .write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    fn rebind(&mut self) {
+        self.bind_grp = create_bind_group(
+            &self.gpu.device,
+            &self.bgl,
+            &self.sampler,
+            &self.params_buf,
+            &self.frame,
+            &self.prev_frame,
+        );
+    }
+
+    /// Recompute the UV transforms for the current frame and surface sizes
+    /// and upload the shader parameters.
+    fn update_layout(&mut self) {
+        // Surface size upright as seen on screen, in physical pixels.
+        let (sw, sh) = match self.params.transform % 2 {
+            1 => (self.config.height as f32, self.config.width as f32),
+            _ => (self.config.width as f32, self.config.height as f32),
+        };
+        (self.params.uv_scale, self.params.uv_offset) = self.placement(sw, sh, &self.frame);
+        (self.params.prev_uv_scale, self.params.prev_uv_offset) =
+            self.placement(sw, sh, &self.prev_frame);
+        self.params.tile = (self.scale_mode == ScaleMode::Tile) as u32;
+        self.gpu
+            .queue
+            .write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    /// UV scale and offset placing `frame` on a `sw`×`sh` surface.
+    fn placement(&self, sw: f32, sh: f32, frame: &FrameTexture) -> ([f32; 2], [f32; 2]) {
+        let (tw, th) = (frame.width as f32, frame.height as f32);
+
+        // Displayed video size in surface pixels.
+        let (dw, dh) = match self.scale_mode {
+            ScaleMode::Stretch => (sw, sh),
+            ScaleMode::Fit => {
+                let k = (sw / tw).min(sh / th);
+                (tw * k, th * k)
+            }
+            ScaleMode::Fill => {
+                let k = (sw / tw).max(sh / th);
+                (tw * k, th * k)
+            }
+            ScaleMode::Center | ScaleMode::Tile => (tw, th),
+        };
+
+        // Texture UV = surface UV × scale + offset, video centred.
+        (
+            [sw / dw, sh / dh],
+            [(dw - sw) / (2.0 * dw), (dh - sh) / (2.0 * dh)],
+        )
+    }
+
+    /// Reconfigure the swapchain after the layer surface changed size.
     ///
-    /// `bgra` must be exactly `width * height * 4` bytes.
-    pub fn upload_and_render(&self, bgra: &[u8]) {
+    /// `width` and `height` are the buffer size in physical pixels.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        if width == self.config.width && height == self.config.height {
+            return;
+        }
+        self.config.width = width;
+        self.config.height = height;
+        self.surface.configure(&self.gpu.device, &self.config);
+        self.update_layout();
+    }
+
+    /// Upload the first frame of a new video and start blending to it from
+    /// the frame on screen.  Like `upload`, a short frame is dropped and
+    /// `false` returned; the frame on screen and any running transition are
+    /// then left alone.
+    pub fn upload_first(
+        &mut self,
+        bgra: &[u8],
+        width: u32,
+        height: u32,
+        transition: Transition,
+        duration: Duration,
+    ) -> bool {
+        if !frame_fits(bgra, width, height) {
+            return false;
+        }
+        self.begin_transition(transition, duration);
+        self.upload(bgra, width, height)
+    }
+
+    /// Start blending from the frame on screen to the frames uploaded from
+    /// now on.
+    fn begin_transition(&mut self, transition: Transition, duration: Duration) {
+        if transition == Transition::None || duration.is_zero() {
+            self.transition = Transition::None;
+            return;
+        }
+        // The outgoing frame becomes the previous texture; the old previous
+        // texture is reused for the incoming video if the size fits.
+        std::mem::swap(&mut self.frame, &mut self.prev_frame);
+        self.rebind();
+        self.update_layout();
+        self.transition = transition;
+        self.transition_start = Instant::now();
+        self.transition_len = duration;
+    }
+
+    /// Whether a transition is running and frames must be drawn even when the
+    /// video has none to offer.
+    pub fn in_transition(&self) -> bool {
+        self.transition != Transition::None
+    }
+
+    /// Free the frame textures, keeping 1×1 placeholders.  The next upload
+    /// recreates the texture at the frame size.
+    pub fn release_frames(&mut self) {
+        self.frame = FrameTexture::new(&self.gpu.device, 1, 1);
+        self.prev_frame = FrameTexture::new(&self.gpu.device, 1, 1);
+        self.transition = Transition::None;
+        self.rebind();
+        self.update_layout();
+    }
+
+    /// Upload `bgra` pixels directly (zero-copy from the GstBuffer map) into
+    /// the frame texture.
+    ///
+    /// `bgra` must hold at least `width * height * 4` bytes; shorter frames
+    /// are dropped and `false` is returned.  The frame texture is recreated
+    /// when the frame size changes, e.g. right after a resize.
+    pub fn upload(&mut self, bgra: &[u8], width: u32, height: u32) -> bool {
+        if !frame_fits(bgra, width, height) {
+            return false;
+        }
+        if width != self.frame.width || height != self.frame.height {
+            self.frame = FrameTexture::new(&self.gpu.device, width, height);
+            self.rebind();
+            self.update_layout();
+        }
+
         // Write pixels straight from the mapped GstBuffer into the GPU texture.
         // wgpu does a single staging-buffer write — no Vec allocation here.
-        self.queue.write_texture(
+        self.gpu.queue.write_texture(
             wgpu::TexelCopyTextureInfo {
-                texture: &self.texture,
+                texture: &self.frame.texture,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
@@ -265,27 +665,65 @@ impl GpuRenderer {
             bgra,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
-                bytes_per_row: Some(self.width * 4),
+                bytes_per_row: Some(width * 4),
                 rows_per_image: None,
             },
             wgpu::Extent3d {
-                width: self.width,
-                height: self.height,
+                width,
+                height,
                 depth_or_array_layers: 1,
             },
         );
+        true
+    }
+
+    /// Draw the current frame (blended with the outgoing one while a
+    /// transition runs) onto the swapchain surface.  Returns `false` when
+    /// nothing was presented, i.e. the surface was not committed.
+    pub fn render(&mut self) -> bool {
+        if self.transition != Transition::None {
+            let t =
+                self.transition_start.elapsed().as_secs_f32() / self.transition_len.as_secs_f32();
+            if t >= 1.0 {
+                self.transition = Transition::None;
+            }
+            self.params.transition = self.transition as u32;
+            self.params.progress = t.min(1.0);
+            self.gpu
+                .queue
+                .write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&self.params));
+        } else if self.params.transition != Transition::None as u32 {
+            self.params.transition = Transition::None as u32;
+            self.gpu
+                .queue
+                .write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&self.params));
+        }
 
         let frame = match self.surface.get_current_texture() {
             Ok(f) => f,
+            // The swapchain no longer matches the surface; reconfigure and
+            // pick up the next frame.
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                self.surface.configure(&self.gpu.device, &self.config);
+                return false;
+            }
+            // No image was free in time, e.g. while the compositor holds
+            // them all; the next frame callback tries again.
+            Err(wgpu::SurfaceError::Timeout) => return false,
             Err(e) => {
-                eprintln!("q6w: wgpu surface error: {e}");
-                return;
+                if !self.surface_failing {
+                    self.surface_failing = true;
+                    eprintln!("q6w: wgpu surface error: {e}");
+                }
+                return false;
             }
         };
+        self.surface_failing = false;
         let view = frame
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
         let mut enc = self
+            .gpu
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("frame_enc"),
@@ -297,7 +735,12 @@ impl GpuRenderer {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: self.params.letterbox[0] as f64,
+                            g: self.params.letterbox[1] as f64,
+                            b: self.params.letterbox[2] as f64,
+                            a: 1.0,
+                        }),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -309,7 +752,72 @@ impl GpuRenderer {
             rpass.set_bind_group(0, &self.bind_grp, &[]);
             rpass.draw(0..6, 0..1);
         }
-        self.queue.submit([enc.finish()]);
+        self.gpu.queue.submit([enc.finish()]);
         frame.present();
+        true
     }
 }
+
+/// Whether `bgra` holds a whole `width`×`height` frame.
+fn frame_fits(bgra: &[u8], width: u32, height: u32) -> bool {
+    width != 0 && height != 0 && bgra.len() >= (width as usize) * (height as usize) * 4
+}
+
+/// Tiling needs a repeating sampler; everything else clamps so the video's
+/// edge pixels don't bleed around the letterbox.
+fn create_sampler(device: &wgpu::Device, scale_mode: ScaleMode, filter: Filter) -> wgpu::Sampler {
+    let address_mode = match scale_mode {
+        ScaleMode::Tile => wgpu::AddressMode::Repeat,
+        _ => wgpu::AddressMode::ClampToEdge,
+    };
+    let filter_mode = match filter {
+        Filter::Nearest => wgpu::FilterMode::Nearest,
+        Filter::Linear => wgpu::FilterMode::Linear,
+    };
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        mag_filter: filter_mode,
+        min_filter: filter_mode,
+        ..Default::default()
+    })
+}
+
+/// Create the bind group sampling the current and previous frame textures.
+fn create_bind_group(
+    device: &wgpu::Device,
+    bgl: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    params_buf: &wgpu::Buffer,
+    frame: &FrameTexture,
+    prev_frame: &FrameTexture,
+) -> wgpu::BindGroup {
+    let tex_view = frame
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor::default());
+    let prev_view = prev_frame
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bg"),
+        layout: bgl,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&tex_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: params_buf.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&prev_view),
+            },
+        ],
+    })
+}
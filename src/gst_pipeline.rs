@@ -12,35 +12,161 @@
 //!
 //! Frame delivery is **zero-copy**: callers receive a `&[u8]` slice mapped
 //! directly from the GstBuffer — no `Vec` is ever allocated.
+//!
+//! Looping uses segment seeks: after the first preroll the whole file is
+//! played as a segment, and each `SegmentDone` queues a non-flushing seek
+//! back to the start.  Decoders (and the VAAPI context) stay alive and the
+//! running time keeps going, so the loop seam is frame-perfect.
+//!
+//! Frames are scaled in the pipeline to the size they will be shown at for
+//! the chosen scale mode, with square pixels, so the GPU only positions them.
+//! The framerate cap (videorate's `max-rate`) and the volume are element
+//! properties, so both change on a playing pipeline without a rebuild.
+//!
+//! The appsink hands frames over a little ahead of their running time (see
+//! `set_refresh`); `with_frame_due` then picks the one closest to the vblank
+//! the next commit lands on.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use gstreamer as gst;
 use gstreamer::prelude::*;
 use gstreamer_app as gst_app;
 
+use crate::gpu_renderer::{Filter, ScaleMode};
+use crate::wake::Waker;
+
+/// How early the appsink releases frames until the output's refresh rate is
+/// known: a 60 Hz frame and a half.
+const DEFAULT_FRAME_LEAD: Duration = Duration::from_millis(25);
+
+/// Frames held back for upcoming vblanks; older ones are dropped.
+const MAX_PENDING: usize = 3;
+
+/// A held frame due further ahead than this predates a flushing seek.
+const STALE_AFTER: Duration = Duration::from_secs(1);
+
+/// Playback settings shared by every pipeline.
+#[derive(Clone, Copy, Debug)]
+pub struct PipelineOptions {
+    pub enable_audio: bool,
+    pub volume: f64,
+    pub fps: Option<i32>,
+    pub scale_mode: ScaleMode,
+    pub filter: Filter,
+}
+
+/// Everything the output caps are computed from.  Shared with the pad-added
+/// callback, which fills in the source size once the decoder knows it.
+struct OutputCaps {
+    /// Surface size in physical pixels.
+    width: i32,
+    height: i32,
+    scale_mode: ScaleMode,
+    filter: Filter,
+    /// Display size of the decoded video, pixel-aspect-ratio applied.
+    source: Option<(i32, i32)>,
+}
+
+impl OutputCaps {
+    /// Size to scale decoded frames to, or `None` while it depends on a
+    /// source size that isn't known yet.
+    fn frame_size(&self) -> Option<(i32, i32)> {
+        // Nearest-neighbour scaling happens on the GPU, on unscaled frames.
+        let mode = match self.filter {
+            Filter::Nearest => ScaleMode::Center,
+            Filter::Linear => self.scale_mode,
+        };
+        match (mode, self.source) {
+            (ScaleMode::Stretch, _) => Some((self.width, self.height)),
+            (_, None) => None,
+            (ScaleMode::Fit | ScaleMode::Fill, Some((src_w, src_h))) => {
+                let kx = self.width as f64 / src_w as f64;
+                let ky = self.height as f64 / src_h as f64;
+                let k = if mode == ScaleMode::Fit {
+                    kx.min(ky)
+                } else {
+                    kx.max(ky)
+                };
+                Some((
+                    ((src_w as f64 * k).round() as i32).max(1),
+                    ((src_h as f64 * k).round() as i32).max(1),
+                ))
+            }
+            (ScaleMode::Center | ScaleMode::Tile, Some(src)) => Some(src),
+        }
+    }
+
+    /// Caps forced at the end of the video chain: square-pixel BGRA at
+    /// `frame_size`.
+    fn caps(&self) -> gst::Caps {
+        let mut caps_builder = gst::Caps::builder("video/x-raw")
+            .field("format", "BGRA")
+            .field("pixel-aspect-ratio", gst::Fraction::new(1, 1));
+        if let Some((w, h)) = self.frame_size() {
+            caps_builder = caps_builder.field("width", w).field("height", h);
+        }
+        caps_builder.build()
+    }
+}
+
+/// Result of draining the bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusStatus {
+    Ok,
+    /// The video reached its end and looping is off.
+    Finished,
+    /// Unrecoverable GStreamer error; already reported on stderr.
+    Fatal,
+}
+
 pub struct Pipeline {
     pipeline: gst::Pipeline,
+    src: gst::Element,
     appsink: gst_app::AppSink,
     bus: gst::Bus,
     /// `true` when VAAPI was unavailable and the software path is active.
     is_software: bool,
-    /// Reference to the volume element for runtime volume control.
+    /// `true` once the initial segment seek has been issued.
+    segment_looping: Cell<bool>,
+    /// Loop at the end of the file; otherwise report `BusStatus::Finished`.
+    looping: Cell<bool>,
+    /// Drops frames above the framerate cap.
+    rate: gst::Element,
+    /// `vapostproc` or `videoscale`, whose method follows the filter.
+    scaler: gst::Element,
+    /// Output capsfilter; its caps are swapped when the surface is resized.
+    cfilter: gst::Element,
+    out_caps: Arc<Mutex<OutputCaps>>,
+    /// Reference to the volume element for runtime volume control; `None`
+    /// when audio is disabled or no audio output could be set up.
     volume_element: Option<gst::Element>,
-    /// Original volume setting for unmuting.
-    original_volume: f64,
+    /// Volume to restore when unmuting.
+    original_volume: Cell<f64>,
+    muted: Cell<bool>,
+    /// Frames pulled from the appsink that aren't due yet, oldest first.
+    pending: RefCell<VecDeque<gst::Sample>>,
 }
 
 impl Pipeline {
-    /// Build the decode pipeline for `path` at `width × height`.
+    /// Build the decode pipeline for `path` on a `width × height` surface
+    /// (physical pixels).
     ///
     /// When `enable_audio` is `true`, an audio playback chain is added;
-    /// otherwise audio pads from uridecodebin are sent to `fakesink`.
+    /// otherwise audio pads from uridecodebin are sent to `fakesink` and the
+    /// pipeline runs on the system clock.
+    ///
+    /// `waker` is signalled whenever a frame is ready or a bus message is
+    /// posted.
     pub fn new(
         path: &str,
-        enable_audio: bool,
-        volume: f64,
+        options: &PipelineOptions,
         width: i32,
         height: i32,
-        fps: Option<i32>,
+        waker: &Waker,
     ) -> Self {
         gst::init().expect(
             "q6w: GStreamer init failed — is GStreamer installed?\n\
@@ -48,26 +174,59 @@ impl Pipeline {
              gst-plugins-bad",
         );
 
-        let uri = if path.starts_with('/') {
-            format!("file://{path}")
-        } else {
-            let cwd = std::env::current_dir().unwrap_or_default();
-            format!("file://{}/{path}", cwd.display())
+        // Callers check the path; a bad one fails on the bus like a missing
+        // file would.
+        let uri = file_uri(path).unwrap_or_default();
+
+        let out_caps = || {
+            Arc::new(Mutex::new(OutputCaps {
+                width,
+                height,
+                scale_mode: options.scale_mode,
+                filter: options.filter,
+                source: None,
+            }))
         };
 
-        if let Some(p) = Self::try_vaapi(&uri, enable_audio, volume, width, height, fps) {
-            eprintln!("q6w: using VAAPI hardware decoder");
-            return p;
-        }
+        let pipeline = match Self::try_vaapi(&uri, options, out_caps()) {
+            Some(p) => {
+                eprintln!("q6w: using VAAPI hardware decoder");
+                p
+            }
+            None => {
+                eprintln!("q6w: WARNING: VAAPI hardware decoding is not available.");
+                eprintln!(
+                    "q6w:   Possible causes: missing VA-API driver, NVIDIA without nouveau/nvidia-vaapi-driver,"
+                );
+                eprintln!("q6w:   or unsupported GPU. Run `vainfo` to diagnose.");
+                eprintln!("q6w:   Falling back to software decoding (higher CPU and RAM usage).");
+                Self::build_software(&uri, options, out_caps())
+            }
+        };
+        pipeline.wake_on_activity(waker);
+        pipeline.set_refresh(None);
+        pipeline
+    }
 
-        eprintln!("q6w: WARNING: VAAPI hardware decoding is not available.");
-        eprintln!(
-            "q6w:   Possible causes: missing VA-API driver, NVIDIA without nouveau/nvidia-vaapi-driver,"
+    // The appsink keeps its samples for `with_latest_frame`; the callback
+    // only pokes the main loop.  Bus messages are still queued for
+    // `handle_bus`, the sync handler runs on the posting thread first.
+
+    fn wake_on_activity(&self, waker: &Waker) {
+        let on_sample = waker.clone();
+        self.appsink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |_| {
+                    on_sample.wake();
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
         );
-        eprintln!("q6w:   or unsupported GPU. Run `vainfo` to diagnose.");
-        eprintln!("q6w:   Falling back to software decoding (higher CPU and RAM usage).");
-
-        Self::build_software(&uri, enable_audio, volume, width, height, fps)
+        let on_message = waker.clone();
+        self.bus.set_sync_handler(move |_, _| {
+            on_message.wake();
+            gst::BusSyncReply::Pass
+        });
     }
 
     // Clamp every internal `multiqueue` to 2 buffers and every internal
@@ -100,11 +259,8 @@ impl Pipeline {
 
     fn try_vaapi(
         uri: &str,
-        enable_audio: bool,
-        volume: f64,
-        width: i32,
-        height: i32,
-        fps: Option<i32>,
+        options: &PipelineOptions,
+        out_caps: Arc<Mutex<OutputCaps>>,
     ) -> Option<Pipeline> {
         gst::ElementFactory::find("vapostproc")?;
 
@@ -124,23 +280,16 @@ impl Pipeline {
             .ok()?;
 
         let postproc = gst::ElementFactory::make("vapostproc").build().ok()?;
+        set_scale_method(&postproc, options.filter);
 
         let rate = gst::ElementFactory::make("videorate")
             .property("drop-only", true)
+            .property("max-rate", max_rate(options.fps))
             .build()
             .ok()?;
 
-        let mut caps_builder = gst::Caps::builder("video/x-raw")
-            .field("format", "BGRA")
-            .field("width", width)
-            .field("height", height);
-        if let Some(f) = fps {
-            caps_builder = caps_builder.field("framerate", gst::Fraction::new(f, 1));
-        }
-        let out_caps = caps_builder.build();
-
         let cfilter = gst::ElementFactory::make("capsfilter")
-            .property("caps", &out_caps)
+            .property("caps", out_caps.lock().unwrap().caps())
             .build()
             .ok()?;
 
@@ -150,12 +299,6 @@ impl Pipeline {
             .sync(true)
             .build();
 
-        // Always attach a real audio sink so GStreamer has a clock provider.
-        // Without -a (audio), volume is set to 0 — silent but clocked.
-        let effective_volume = if enable_audio { volume } else { 0.0 };
-        let (aqueue, aconvert, aresample, vol, audiosink) =
-            Self::make_audio_chain(effective_volume)?;
-
         pipeline
             .add_many([
                 &src,
@@ -164,28 +307,32 @@ impl Pipeline {
                 &rate,
                 &cfilter,
                 appsink.upcast_ref::<gst::Element>(),
-                &aqueue,
-                &aconvert,
-                &aresample,
-                &vol,
-                &audiosink,
             ])
             .ok()?;
 
         gst::Element::link_many([&vqueue, &postproc, &rate, &cfilter, appsink.upcast_ref()])
             .ok()?;
-        gst::Element::link_many([&aqueue, &aconvert, &aresample, &vol, &audiosink]).ok()?;
+        let (audio, volume_element) = Self::add_audio(&pipeline, options)?;
 
-        Self::wire_pads(&src, &vqueue, Some(&aqueue));
+        Self::wire_pads(&src, &vqueue, &audio, &cfilter, &out_caps);
 
         let bus = pipeline.bus().expect("no bus");
         Some(Pipeline {
             pipeline,
+            src,
             appsink,
             bus,
             is_software: false,
-            volume_element: Some(vol.clone()),
-            original_volume: effective_volume,
+            segment_looping: Cell::new(false),
+            looping: Cell::new(true),
+            rate,
+            scaler: postproc,
+            cfilter,
+            out_caps,
+            volume_element,
+            original_volume: Cell::new(options.volume),
+            muted: Cell::new(false),
+            pending: RefCell::new(VecDeque::new()),
         })
     }
 
@@ -198,11 +345,8 @@ impl Pipeline {
 
     fn build_software(
         uri: &str,
-        enable_audio: bool,
-        volume: f64,
-        width: i32,
-        height: i32,
-        fps: Option<i32>,
+        options: &PipelineOptions,
+        out_caps: Arc<Mutex<OutputCaps>>,
     ) -> Pipeline {
         let pipeline = gst::Pipeline::default();
         Self::install_queue_clamp(&pipeline);
@@ -224,9 +368,11 @@ impl Pipeline {
             .property("add-borders", false)
             .build()
             .expect("videoscale not found");
+        set_scale_method(&scale, options.filter);
 
         let rate = gst::ElementFactory::make("videorate")
             .property("drop-only", true)
+            .property("max-rate", max_rate(options.fps))
             .build()
             .expect("videorate not found");
 
@@ -234,17 +380,8 @@ impl Pipeline {
             .build()
             .expect("videoconvert not found");
 
-        let mut caps_builder = gst::Caps::builder("video/x-raw")
-            .field("format", "BGRA")
-            .field("width", width)
-            .field("height", height);
-        if let Some(f) = fps {
-            caps_builder = caps_builder.field("framerate", gst::Fraction::new(f, 1));
-        }
-        let out_caps = caps_builder.build();
-
         let cfilter = gst::ElementFactory::make("capsfilter")
-            .property("caps", &out_caps)
+            .property("caps", out_caps.lock().unwrap().caps())
             .build()
             .expect("capsfilter not found");
 
@@ -254,12 +391,6 @@ impl Pipeline {
             .sync(true)
             .build();
 
-        // Always attach a real audio sink so GStreamer has a clock provider.
-        // Without -a (audio), volume is set to 0 — silent but clocked.
-        let effective_volume = if enable_audio { volume } else { 0.0 };
-        let (aqueue, aconvert, aresample, vol, audiosink) =
-            Self::make_audio_chain(effective_volume).expect("audio chain elements not found");
-
         pipeline
             .add_many([
                 &src,
@@ -269,11 +400,6 @@ impl Pipeline {
                 &convert,
                 &cfilter,
                 appsink.upcast_ref::<gst::Element>(),
-                &aqueue,
-                &aconvert,
-                &aresample,
-                &vol,
-                &audiosink,
             ])
             .expect("failed to add elements");
 
@@ -286,29 +412,45 @@ impl Pipeline {
             appsink.upcast_ref(),
         ])
         .expect("failed to link video chain");
-        gst::Element::link_many([&aqueue, &aconvert, &aresample, &vol, &audiosink])
-            .expect("failed to link audio chain");
+        let (audio, volume_element) =
+            Self::add_audio(&pipeline, options).expect("fakesink not found");
 
-        Self::wire_pads(&src, &vqueue, Some(&aqueue));
+        Self::wire_pads(&src, &vqueue, &audio, &cfilter, &out_caps);
 
         let bus = pipeline.bus().expect("no bus");
         Pipeline {
             pipeline,
+            src,
             appsink,
             bus,
             is_software: true,
-            volume_element: Some(vol.clone()),
-            original_volume: effective_volume,
+            segment_looping: Cell::new(false),
+            looping: Cell::new(true),
+            rate,
+            scaler: scale,
+            cfilter,
+            out_caps,
+            volume_element,
+            original_volume: Cell::new(options.volume),
+            muted: Cell::new(false),
+            pending: RefCell::new(VecDeque::new()),
         }
     }
 
+    // The video pad's caps carry the source size and pixel-aspect-ratio;
+    // they complete the output caps before the first frame is negotiated.
+
     fn wire_pads(
         src: &gst::Element,
         vqueue: &gst::Element,
-        audio_sink_elem: Option<&gst::Element>,
+        audio: &gst::Element,
+        cfilter: &gst::Element,
+        out_caps: &Arc<Mutex<OutputCaps>>,
     ) {
         let vqueue_w = vqueue.downgrade();
-        let audio_w = audio_sink_elem.map(|e| e.downgrade());
+        let audio_w = audio.downgrade();
+        let cfilter_w = cfilter.downgrade();
+        let out_caps = Arc::clone(out_caps);
         src.connect_pad_added(move |_, pad| {
             let Some(caps) = pad.current_caps() else {
                 return;
@@ -316,6 +458,20 @@ impl Pipeline {
             let Some(s) = caps.structure(0) else { return };
             let name = s.name();
             if name.starts_with("video/") {
+                if let (Ok(w), Ok(h), Some(cfilter)) = (
+                    s.get::<i32>("width"),
+                    s.get::<i32>("height"),
+                    cfilter_w.upgrade(),
+                ) {
+                    let par = s
+                        .get::<gst::Fraction>("pixel-aspect-ratio")
+                        .unwrap_or_else(|_| gst::Fraction::new(1, 1));
+                    let display_w =
+                        (w as i64 * par.numer() as i64 / par.denom().max(1) as i64).max(1) as i32;
+                    let mut out = out_caps.lock().unwrap();
+                    out.source = Some((display_w, h));
+                    cfilter.set_property("caps", out.caps());
+                }
                 if let Some(q) = vqueue_w.upgrade() {
                     let sink = q.static_pad("sink").unwrap();
                     if !sink.is_linked() {
@@ -323,8 +479,7 @@ impl Pipeline {
                     }
                 }
             } else if name.starts_with("audio/")
-                && let Some(ref w) = audio_w
-                && let Some(q) = w.upgrade()
+                && let Some(q) = audio_w.upgrade()
             {
                 let sink = q.static_pad("sink").unwrap();
                 if !sink.is_linked() {
@@ -334,15 +489,54 @@ impl Pipeline {
         });
     }
 
-    fn make_audio_chain(
+    // Audio:
+    //   with --audio:  queue  →  audioconvert  →  audioresample  →  volume
+    //                  →  autoaudiosink, which also provides the clock
+    //   without:       fakesink, and the system clock paces the video
+    //
+    // Only asking for sound needs a sound server or device.
+
+    /// Add the audio branch to `pipeline`.  Returns the element audio pads
+    /// link to, and the volume element if sound is played.
+    fn add_audio(
+        pipeline: &gst::Pipeline,
+        options: &PipelineOptions,
+    ) -> Option<(gst::Element, Option<gst::Element>)> {
+        if options.enable_audio {
+            match Self::add_audio_chain(pipeline, options.volume) {
+                Some((aqueue, vol)) => return Some((aqueue, Some(vol))),
+                None => eprintln!("q6w: no audio output available — playing without sound"),
+            }
+        }
+        // Not async: a file without audio never prerolls it, and the
+        // pipeline would wait in PAUSED for that forever.
+        let fakesink = gst::ElementFactory::make("fakesink")
+            .property("sync", true)
+            .property("async", false)
+            .build()
+            .ok()?;
+        pipeline.add(&fakesink).ok()?;
+        pipeline.use_clock(Some(&gst::SystemClock::obtain()));
+        Some((fakesink, None))
+    }
+
+    /// Add and link the sound output chain; returns its queue and volume
+    /// element.
+    fn add_audio_chain(
+        pipeline: &gst::Pipeline,
         volume: f64,
-    ) -> Option<(
-        gst::Element,
-        gst::Element,
-        gst::Element,
-        gst::Element,
-        gst::Element,
-    )> {
+    ) -> Option<(gst::Element, gst::Element)> {
+        let chain = Self::make_audio_chain(volume)?;
+        pipeline.add_many(&chain).ok()?;
+        if gst::Element::link_many(&chain).is_err() {
+            pipeline.remove_many(&chain).ok();
+            return None;
+        }
+        let [aqueue, _, _, vol, _] = chain;
+        Some((aqueue, vol))
+    }
+
+    fn make_audio_chain(volume: f64) -> Option<[gst::Element; 5]> {
         let aqueue = gst::ElementFactory::make("queue")
             .property("max-size-buffers", 0u32)
             .property("max-size-bytes", 0u32)
@@ -359,7 +553,7 @@ impl Pipeline {
             .property("sync", true)
             .build()
             .ok()?;
-        Some((aqueue, aconvert, aresample, vol, audiosink))
+        Some([aqueue, aconvert, aresample, vol, audiosink])
     }
 
     // ── Playback control ─────────────────────────────────────────────────────
@@ -379,6 +573,15 @@ impl Pipeline {
         }
     }
 
+    /// Drop to NULL, freeing the decoder and its buffers.  `pause` or
+    /// `resume` start over from the beginning of the file.
+    pub fn stop(&self) {
+        self.pending.borrow_mut().clear();
+        self.pipeline.set_state(gst::State::Null).ok();
+        // The segment seek is lost with the state; issue it again on restart.
+        self.segment_looping.set(false);
+    }
+
     pub fn resume(&self) {
         if let Err(e) = self.pipeline.set_state(gst::State::Playing) {
             eprintln!("q6w: failed to resume pipeline: {e:?}");
@@ -386,17 +589,119 @@ impl Pipeline {
     }
 
     pub fn mute(&self) {
+        self.muted.set(true);
         if let Some(ref vol) = self.volume_element {
             vol.set_property("volume", 0.0);
         }
     }
 
     pub fn unmute(&self) {
+        self.muted.set(false);
         if let Some(ref vol) = self.volume_element {
-            vol.set_property("volume", self.original_volume);
+            vol.set_property("volume", self.original_volume.get());
+        }
+    }
+
+    /// Change the volume (0.0–1.0).  While muted it takes effect on unmute.
+    pub fn set_volume(&self, volume: f64) {
+        self.original_volume.set(volume.clamp(0.0, 1.0));
+        if !self.muted.get() {
+            self.unmute();
+        }
+    }
+
+    /// Rescale frames for another scale mode or filter.
+    pub fn set_scaling(&self, scale_mode: ScaleMode, filter: Filter) {
+        let mut out = self.out_caps.lock().unwrap();
+        out.scale_mode = scale_mode;
+        out.filter = filter;
+        set_scale_method(&self.scaler, filter);
+        self.cfilter.set_property("caps", out.caps());
+    }
+
+    /// Change the framerate cap; `None` passes the video's own rate through.
+    ///
+    /// videorate renegotiates by itself, also while playing.  A cap above
+    /// the video's own rate leaves it alone: with `drop-only` it never
+    /// duplicates frames.
+    pub fn set_fps(&self, fps: Option<i32>) {
+        self.rate.set_property("max-rate", max_rate(fps));
+    }
+
+    /// Loop the video (the default), or stop at its end and report
+    /// `BusStatus::Finished` so the caller can move on.
+    pub fn set_looping(&self, looping: bool) {
+        self.looping.set(looping);
+    }
+
+    /// Switch to another file, keeping the pipeline and its elements.
+    ///
+    /// `uridecodebin` only accepts a new URI in READY, so the pipeline drops
+    /// there briefly and then returns to the state it was heading for.  A
+    /// stopped pipeline stays stopped.
+    pub fn set_file(&self, path: &str) {
+        let (_, current, pending) = self.pipeline.state(gst::ClockTime::ZERO);
+        let target = if pending == gst::State::VoidPending {
+            current
+        } else {
+            pending
+        };
+        let stopped = target == gst::State::Null;
+
+        if !stopped {
+            self.pipeline.set_state(gst::State::Ready).ok();
+        }
+        // Drop what is queued about the old file's playback; errors and
+        // warnings still go through `handle_bus`.
+        while self
+            .bus
+            .pop_filtered(&[
+                gst::MessageType::SegmentDone,
+                gst::MessageType::Eos,
+                gst::MessageType::AsyncDone,
+            ])
+            .is_some()
+        {}
+        self.src
+            .set_property("uri", file_uri(path).unwrap_or_default());
+        self.segment_looping.set(false);
+        self.pending.borrow_mut().clear();
+        self.out_caps.lock().unwrap().source = None;
+        if !stopped {
+            self.pipeline.set_state(target.max(gst::State::Paused)).ok();
         }
     }
 
+    /// Length of the current file, once the pipeline has prerolled.
+    pub fn duration(&self) -> Option<Duration> {
+        self.pipeline
+            .query_duration::<gst::ClockTime>()
+            .map(Duration::from)
+    }
+
+    /// A query for the playback position that stays valid after `self` is
+    /// moved, and returns `None` once the pipeline is gone.
+    pub fn position_source(&self) -> impl Fn() -> Option<Duration> + 'static {
+        let pipeline = self.pipeline.downgrade();
+        move || {
+            pipeline
+                .upgrade()?
+                .query_position::<gst::ClockTime>()
+                .map(Duration::from)
+        }
+    }
+
+    /// Renegotiate the output size after the surface was resized.
+    ///
+    /// The capsfilter pushes a reconfigure upstream, so `vapostproc` or
+    /// `videoscale` start producing frames at the new size without a rebuild.
+    pub fn set_size(&self, width: i32, height: i32) {
+        let mut out = self.out_caps.lock().unwrap();
+        out.width = width;
+        out.height = height;
+        self.cfilter.set_property("caps", out.caps());
+    }
+
     /// Drain the appsink and process only the **latest** available frame.
     /// For a wallpaper we never need stale frames — only the freshest one.
     pub fn with_latest_frame<F: FnOnce(&[u8], i32, i32)>(&self, f: F) {
@@ -407,46 +712,187 @@ impl Pipeline {
         while let Some(newer) = self.appsink.try_pull_sample(gst::ClockTime::ZERO) {
             last = Some(newer);
         }
-        let sample = last.unwrap();
-        let Some(buffer) = sample.buffer() else {
-            return;
-        };
-        let Some(caps) = sample.caps() else { return };
-        let Some(s) = caps.structure(0) else { return };
-        let Ok(w) = s.get::<i32>("width") else { return };
-        let Ok(h) = s.get::<i32>("height") else {
-            return;
-        };
-        let Ok(map) = buffer.map_readable() else {
-            return;
+        map_frame(&last.unwrap(), f);
+    }
+
+    /// Have the appsink release frames a refresh period and a half before
+    /// their running time, so one is at hand when the vblank it belongs to
+    /// is being prepared.  `refresh` is `None` when unknown.
+    pub fn set_refresh(&self, refresh: Option<Duration>) {
+        let lead = refresh.map_or(DEFAULT_FRAME_LEAD, |period| period * 3 / 2);
+        let lead = i64::try_from(lead.as_nanos()).unwrap_or(i64::MAX);
+        self.appsink.set_property("ts-offset", -lead);
+    }
+
+    /// Hand the frame due `delay` from now to `f`: the newest one whose
+    /// running time is at most `delay + slack` away.  Earlier frames are
+    /// dropped, later ones kept for the following vblanks.
+    ///
+    /// Returns how long until the next kept frame is due, if any.  Before
+    /// the pipeline plays on a clock the newest frame is taken.
+    pub fn with_frame_due<F: FnOnce(&[u8], i32, i32)>(
+        &self,
+        delay: Duration,
+        slack: Duration,
+        f: F,
+    ) -> Option<Duration> {
+        let mut pending = self.pending.borrow_mut();
+        while let Some(sample) = self.appsink.try_pull_sample(gst::ClockTime::ZERO) {
+            pending.push_back(sample);
+        }
+        while pending.len() > MAX_PENDING {
+            pending.pop_front();
+        }
+
+        let now = self.running_time();
+        let chosen = match now {
+            None => pending.len().checked_sub(1),
+            Some(now) => {
+                let target = now + delay + slack;
+                pending.retain(|s| running_time(s).is_none_or(|t| t <= target + STALE_AFTER));
+                pending
+                    .iter()
+                    .rposition(|s| running_time(s).is_none_or(|t| t <= target))
+            }
         };
-        f(map.as_slice(), w, h);
+        if let Some(i) = chosen {
+            let sample = pending.drain(..=i).next_back().expect("index in range");
+            map_frame(&sample, f);
+        }
+
+        let next = running_time(pending.front()?)?;
+        Some(next.saturating_sub(now? + slack))
+    }
+
+    /// Running time on the pipeline clock, while the pipeline plays.  The
+    /// clock runs on in PAUSED and the base time only catches up on the
+    /// next PLAYING, so the difference would count the pause as played.
+    fn running_time(&self) -> Option<Duration> {
+        if self.pipeline.current_state() != gst::State::Playing {
+            return None;
+        }
+        let clock = self.pipeline.clock()?;
+        let now = clock.time()?;
+        let base = self.pipeline.base_time()?;
+        Some(now.checked_sub(base)?.into())
     }
 
-    /// Drain pending bus messages.  Returns `true` on fatal error.
-    pub fn handle_bus(&self) -> bool {
+    /// Jump back to the start after EOS: a flushing seek if possible, a full
+    /// NULL → PLAYING cycle otherwise.
+    fn restart(&self) {
+        self.pending.borrow_mut().clear();
+        if self
+            .pipeline
+            .seek_simple(gst::SeekFlags::FLUSH, gst::ClockTime::ZERO)
+            .is_err()
+        {
+            self.pipeline.set_state(gst::State::Null).ok();
+            self.pipeline.set_state(gst::State::Playing).ok();
+        }
+    }
+
+    /// Drain pending bus messages.
+    pub fn handle_bus(&self) -> BusStatus {
         while let Some(msg) = self.bus.pop() {
             use gst::MessageView;
             match msg.view() {
-                MessageView::Eos(..) => {
-                    self.pipeline.set_state(gst::State::Null).ok();
-                    self.pipeline.set_state(gst::State::Playing).ok();
+                // First preroll done: replay the file as a segment so its end
+                // posts SegmentDone instead of EOS.
+                MessageView::AsyncDone(..) if !self.segment_looping.get() => {
+                    self.segment_looping.set(true);
+                    if let Err(e) = self.pipeline.seek_simple(
+                        gst::SeekFlags::FLUSH | gst::SeekFlags::SEGMENT,
+                        gst::ClockTime::ZERO,
+                    ) {
+                        eprintln!("q6w: segment seek failed, looping on EOS instead: {e}");
+                    }
                 }
+                MessageView::SegmentDone(..) | MessageView::Eos(..) if !self.looping.get() => {
+                    return BusStatus::Finished;
+                }
+                MessageView::SegmentDone(..) => {
+                    // Non-flushing: decoding carries straight on from the start.
+                    let looped = self
+                        .pipeline
+                        .seek_simple(gst::SeekFlags::SEGMENT, gst::ClockTime::ZERO)
+                        .is_ok();
+                    if !looped {
+                        self.restart();
+                    }
+                }
+                // Only reached when segment seeks aren't supported.
+                MessageView::Eos(..) => self.restart(),
                 MessageView::Error(e) => {
                     eprintln!(
                         "q6w: GStreamer error: {}\n  debug: {}",
                         e.error(),
                         e.debug().unwrap_or_default(),
                     );
-                    return true;
+                    return BusStatus::Fatal;
                 }
                 _ => {}
             }
         }
-        false
+        BusStatus::Ok
+    }
+}
+
+/// Map `sample`'s BGRA pixels and pass them to `f` with the frame size.
+fn map_frame<F: FnOnce(&[u8], i32, i32)>(sample: &gst::Sample, f: F) {
+    let Some(buffer) = sample.buffer() else {
+        return;
+    };
+    let Some(caps) = sample.caps() else { return };
+    let Some(s) = caps.structure(0) else { return };
+    let Ok(w) = s.get::<i32>("width") else { return };
+    let Ok(h) = s.get::<i32>("height") else {
+        return;
+    };
+    let Ok(map) = buffer.map_readable() else {
+        return;
+    };
+    f(map.as_slice(), w, h);
+}
+
+/// When `sample` is due, as running time in its segment.
+fn running_time(sample: &gst::Sample) -> Option<Duration> {
+    let pts = sample.buffer()?.pts()?;
+    let segment = sample.segment()?.downcast_ref::<gst::ClockTime>()?;
+    segment.to_running_time(pts).map(Duration::from)
+}
+
+/// Scale the way `filter` asks even before the GPU does: the pixel-aspect
+/// correction goes through `scaler` and must not blur pixel art.
+/// `vapostproc` has no nearest-neighbour mode; `fast` comes closest.
+fn set_scale_method(scaler: &gst::Element, filter: Filter) {
+    let nearest = filter == Filter::Nearest;
+    if scaler.find_property("method").is_some() {
+        let method = if nearest {
+            "nearest-neighbour"
+        } else {
+            "bilinear"
+        };
+        scaler.set_property_from_str("method", method);
+    } else if scaler.find_property("scale-method").is_some() {
+        let method = if nearest { "fast" } else { "default" };
+        scaler.set_property_from_str("scale-method", method);
     }
 }
 
+/// videorate's `max-rate` for a framerate cap.
+fn max_rate(fps: Option<i32>) -> i32 {
+    fps.map_or(i32::MAX, |fps| fps.max(1))
+}
+
+/// Escaped `file://` URI for `path`, resolving relative paths against the
+/// cwd.
+pub fn file_uri(path: &str) -> Result<String, String> {
+    let path = std::path::absolute(path).map_err(|e| format!("{path}: {e}"))?;
+    gst::glib::filename_to_uri(&path, None)
+        .map(String::from)
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
 impl Drop for Pipeline {
     fn drop(&mut self) {
         self.pipeline.set_state(gst::State::Null).ok();
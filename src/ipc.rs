@@ -0,0 +1,513 @@
+// SPDX-License-Identifier: AGPL-3.0-only
+// Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
+
+//! Runtime control over a Unix socket.
+//!
+//! A running q6w listens on `$XDG_RUNTIME_DIR/q6w-$WAYLAND_DISPLAY.sock`.
+//! Clients send one command per line and get one JSON object per line back:
+//!
+//! ```text
+//! pause | resume | mute | unmute | next | status | subscribe
+//! set-file [NAME=]PATH     (NAME limits the change to one output)
+//! volume 0.0–1.0
+//! fps N | fps none
+//! ```
+//!
+//! After `subscribe`, the connection also receives an `{"event": …}` line
+//! whenever playback pauses, resumes, mutes or a video changes, so status
+//! bars can follow the wallpaper.  `q6w ctl` is the bundled client, but
+//! `socat` or a few lines of shell work just as well.
+//!
+//! All sockets are non-blocking and polled from the main loop.  A client
+//! that stops reading and fills its socket buffer is disconnected rather
+//! than allowed to stall rendering.
+
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+/// A command received from a client.
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    Pause,
+    Resume,
+    Mute,
+    Unmute,
+    /// Play `path`, on the output named `output` or on all outputs.
+    SetFile {
+        output: Option<String>,
+        path: String,
+    },
+    Volume(f64),
+    /// Skip to the next playlist video.
+    Next,
+    /// New framerate cap; `None` removes it.
+    Fps(Option<i32>),
+    Status,
+}
+
+impl Request {
+    /// Parse one request line.  `subscribe` is handled by the server itself.
+    fn parse(line: &str) -> Result<Self, String> {
+        let (cmd, arg) = match line.split_once(' ') {
+            Some((cmd, arg)) => (cmd, arg.trim()),
+            None => (line, ""),
+        };
+        let no_arg = |req| {
+            if arg.is_empty() {
+                Ok(req)
+            } else {
+                Err(format!("{cmd} takes no argument"))
+            }
+        };
+        match cmd {
+            "pause" => no_arg(Request::Pause),
+            "resume" => no_arg(Request::Resume),
+            "mute" => no_arg(Request::Mute),
+            "unmute" => no_arg(Request::Unmute),
+            "next" => no_arg(Request::Next),
+            "status" => no_arg(Request::Status),
+            "set-file" => {
+                if arg.is_empty() {
+                    return Err("usage: set-file [NAME=]PATH".into());
+                }
+                // `NAME=PATH` as with --output; a `/` before the `=` means
+                // the `=` is part of the path.
+                match arg.split_once('=') {
+                    Some((name, path)) if !name.is_empty() && !name.contains('/') => {
+                        Ok(Request::SetFile {
+                            output: Some(name.to_owned()),
+                            path: path.to_owned(),
+                        })
+                    }
+                    _ => Ok(Request::SetFile {
+                        output: None,
+                        path: arg.to_owned(),
+                    }),
+                }
+            }
+            "volume" => match arg.parse::<f64>() {
+                Ok(v) if (0.0..=1.0).contains(&v) => Ok(Request::Volume(v)),
+                _ => Err("usage: volume 0.0–1.0".into()),
+            },
+            "fps" => match arg {
+                "none" | "" => Ok(Request::Fps(None)),
+                n => match n.parse::<i32>() {
+                    Ok(f) if f > 0 => Ok(Request::Fps(Some(f))),
+                    _ => Err("usage: fps N | fps none".into()),
+                },
+            },
+            "" => Err("empty command".into()),
+            _ => Err(format!("unknown command `{cmd}`")),
+        }
+    }
+}
+
+/// State change pushed to subscribers.
+pub enum Event<'a> {
+    /// `output` was paused; `reason` is the most explicit of what holds
+    /// it: `user`, `power-off`, `fullscreen`, `window`, `idle`, `battery`,
+    /// `hidden`.
+    Paused {
+        output: &'a str,
+        reason: &'a str,
+    },
+    Resumed {
+        output: &'a str,
+    },
+    Muted {
+        reason: &'a str,
+    },
+    Unmuted,
+    /// `output` started showing `file`.
+    Video {
+        output: &'a str,
+        file: &'a str,
+    },
+    Volume(f64),
+    Fps(Option<i32>),
+}
+
+impl Event<'_> {
+    fn to_json(&self) -> String {
+        match self {
+            Event::Paused { output, reason } => format!(
+                r#"{{"event":"paused","output":{},"reason":{}}}"#,
+                json_string(output),
+                json_string(reason)
+            ),
+            Event::Resumed { output } => {
+                format!(r#"{{"event":"resumed","output":{}}}"#, json_string(output))
+            }
+            Event::Muted { reason } => {
+                format!(r#"{{"event":"muted","reason":{}}}"#, json_string(reason))
+            }
+            Event::Unmuted => r#"{"event":"unmuted"}"#.to_owned(),
+            Event::Video { output, file } => format!(
+                r#"{{"event":"video","output":{},"file":{}}}"#,
+                json_string(output),
+                json_string(file)
+            ),
+            Event::Volume(v) => format!(r#"{{"event":"volume","volume":{v}}}"#),
+            Event::Fps(fps) => format!(r#"{{"event":"fps","fps":{}}}"#, json_fps(*fps)),
+        }
+    }
+}
+
+/// Answer to a `status` request.
+pub struct Status<'a> {
+    /// `user` while paused over IPC, MPRIS or SIGUSR1.
+    pub paused: Option<&'a str>,
+    /// Why audio is muted, most explicit first.
+    pub muted: Vec<&'a str>,
+    pub volume: f64,
+    pub fps: Option<i32>,
+    pub outputs: Vec<OutputStatus<'a>>,
+}
+
+pub struct OutputStatus<'a> {
+    pub name: &'a str,
+    pub file: &'a str,
+    /// Why this output is paused, most explicit first.
+    pub paused: Vec<&'a str>,
+    /// Surface size in logical pixels.
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Status<'_> {
+    pub fn to_json(&self) -> String {
+        let reason = |r: Option<&&str>| r.map_or("null".to_owned(), |r| json_string(r));
+        let reasons = |r: &[&str]| {
+            let r: Vec<String> = r.iter().map(|r| json_string(r)).collect();
+            format!("[{}]", r.join(","))
+        };
+        let outputs: Vec<String> = self
+            .outputs
+            .iter()
+            .map(|o| {
+                format!(
+                    r#"{{"name":{},"file":{},"paused":{},"pause_reason":{},"pause_reasons":{},"width":{},"height":{}}}"#,
+                    json_string(o.name),
+                    json_string(o.file),
+                    !o.paused.is_empty(),
+                    reason(o.paused.first()),
+                    reasons(&o.paused),
+                    o.width,
+                    o.height
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"ok":true,"paused":{},"pause_reason":{},"muted":{},"mute_reason":{},"volume":{},"fps":{},"outputs":[{}]}}"#,
+            self.paused.is_some(),
+            reason(self.paused.as_ref()),
+            !self.muted.is_empty(),
+            reason(self.muted.first()),
+            self.volume,
+            json_fps(self.fps),
+            outputs.join(",")
+        )
+    }
+}
+
+pub const OK: &str = r#"{"ok":true}"#;
+
+pub fn error(msg: &str) -> String {
+    format!(r#"{{"ok":false,"error":{}}}"#, json_string(msg))
+}
+
+fn json_fps(fps: Option<i32>) -> String {
+    fps.map_or("null".to_owned(), |f| f.to_string())
+}
+
+/// Quote `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Where the control socket for the current Wayland display lives.
+pub fn socket_path() -> Result<PathBuf, String> {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").ok_or("XDG_RUNTIME_DIR is not set")?;
+    let display = std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".into());
+    // WAYLAND_DISPLAY may also be an absolute socket path.
+    let display = Path::new(&display)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or(display);
+    Ok(PathBuf::from(dir).join(format!("q6w-{display}.sock")))
+}
+
+/// Identifies the connection a request came from, for the reply.
+pub type ClientId = u64;
+
+struct Client {
+    id: ClientId,
+    stream: UnixStream,
+    /// Bytes received but not yet terminated by a newline.
+    buf: Vec<u8>,
+    subscribed: bool,
+    closed: bool,
+}
+
+impl Client {
+    fn send(&mut self, line: &str) {
+        let mut msg = Vec::with_capacity(line.len() + 1);
+        msg.extend_from_slice(line.as_bytes());
+        msg.push(b'\n');
+        if self.stream.write_all(&msg).is_err() {
+            self.closed = true;
+        }
+    }
+}
+
+pub struct Server {
+    listener: UnixListener,
+    path: PathBuf,
+    clients: Vec<Client>,
+    next_id: ClientId,
+}
+
+impl Server {
+    /// Listen on `socket_path()`.  A stale socket left by a crashed instance
+    /// is replaced; a live one means another q6w owns this display.
+    pub fn bind() -> Result<Self, String> {
+        let path = socket_path()?;
+        let listener = match UnixListener::bind(&path) {
+            Ok(l) => l,
+            Err(e) if e.kind() == ErrorKind::AddrInUse => {
+                if UnixStream::connect(&path).is_ok() {
+                    return Err(format!(
+                        "{} is in use — is another q6w running?",
+                        path.display()
+                    ));
+                }
+                std::fs::remove_file(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+                UnixListener::bind(&path).map_err(|e| format!("{}: {e}", path.display()))?
+            }
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(Server {
+            listener,
+            path,
+            clients: Vec::new(),
+            next_id: 0,
+        })
+    }
+
+    /// File descriptors to poll for readability.
+    pub fn fds(&self) -> Vec<RawFd> {
+        std::iter::once(self.listener.as_raw_fd())
+            .chain(self.clients.iter().map(|c| c.stream.as_raw_fd()))
+            .collect()
+    }
+
+    /// Accept new connections and collect every complete request line.
+    /// Malformed requests and `subscribe` are answered here.
+    pub fn read_requests(&mut self) -> Vec<(ClientId, Request)> {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(Client {
+                    id: self.next_id,
+                    stream,
+                    buf: Vec::new(),
+                    subscribed: false,
+                    closed: false,
+                });
+                self.next_id += 1;
+            }
+        }
+
+        let mut requests = Vec::new();
+        for client in &mut self.clients {
+            let mut chunk = [0u8; 1024];
+            loop {
+                match client.stream.read(&mut chunk) {
+                    Ok(0) => {
+                        client.closed = true;
+                        break;
+                    }
+                    Ok(n) => client.buf.extend_from_slice(&chunk[..n]),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(_) => {
+                        client.closed = true;
+                        break;
+                    }
+                }
+            }
+            while let Some(end) = client.buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = client.buf.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim();
+                if line == "subscribe" {
+                    client.subscribed = true;
+                    client.send(OK);
+                    continue;
+                }
+                match Request::parse(line) {
+                    Ok(req) => requests.push((client.id, req)),
+                    Err(e) => client.send(&error(&e)),
+                }
+            }
+            // Nobody sends commands this long; don't buffer without bound.
+            if client.buf.len() > 64 * 1024 {
+                client.closed = true;
+            }
+        }
+        self.clients.retain(|c| !c.closed);
+        requests
+    }
+
+    /// Send the response to a request from `id`.
+    pub fn reply(&mut self, id: ClientId, json: &str) {
+        if let Some(client) = self.clients.iter_mut().find(|c| c.id == id) {
+            client.send(json);
+        }
+    }
+
+    /// Push `event` to every subscribed client.
+    pub fn broadcast(&mut self, event: &Event) {
+        let json = event.to_json();
+        for client in self.clients.iter_mut().filter(|c| c.subscribed) {
+            client.send(&json);
+        }
+        self.clients.retain(|c| !c.closed);
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// `q6w ctl`: send `request` to the running instance and print the reply.
+/// With `subscribe`, keep printing events until the instance exits.
+/// Returns the process exit code.
+pub fn run_client(request: &str) -> i32 {
+    let path = match socket_path() {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("q6w: {e}");
+            return 1;
+        }
+    };
+    let mut stream = match UnixStream::connect(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!(
+                "q6w: cannot connect to {}: {e} — is q6w running?",
+                path.display()
+            );
+            return 1;
+        }
+    };
+    if let Err(e) = writeln!(stream, "{request}") {
+        eprintln!("q6w: {}: {e}", path.display());
+        return 1;
+    }
+
+    let follow = request == "subscribe";
+    let mut lines = BufReader::new(stream).lines();
+    let Some(Ok(reply)) = lines.next() else {
+        eprintln!("q6w: no reply from {}", path.display());
+        return 1;
+    };
+    if !reply.starts_with(r#"{"ok":true"#) {
+        println!("{reply}");
+        return 1;
+    }
+    if !follow {
+        // A bare acknowledgement isn't worth printing.
+        if reply != OK {
+            println!("{reply}");
+        }
+        return 0;
+    }
+    for line in lines.map_while(Result::ok) {
+        println!("{line}");
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let set_file = |output: Option<&str>, path: &str| Request::SetFile {
+            output: output.map(str::to_owned),
+            path: path.to_owned(),
+        };
+        let cases = [
+            ("pause", Request::Pause),
+            ("resume", Request::Resume),
+            ("mute", Request::Mute),
+            ("unmute", Request::Unmute),
+            ("next", Request::Next),
+            ("status", Request::Status),
+            ("volume 0", Request::Volume(0.0)),
+            ("volume 0.35", Request::Volume(0.35)),
+            ("volume 1", Request::Volume(1.0)),
+            ("fps 24", Request::Fps(Some(24))),
+            ("fps none", Request::Fps(None)),
+            // As sent by `q6w ctl fps`: removes the cap.
+            ("fps", Request::Fps(None)),
+            ("set-file /v/a.mp4", set_file(None, "/v/a.mp4")),
+            ("set-file DP-1=/v/a.mp4", set_file(Some("DP-1"), "/v/a.mp4")),
+            (
+                "set-file DP-1=/My Videos/a b.mp4",
+                set_file(Some("DP-1"), "/My Videos/a b.mp4"),
+            ),
+            // An `=` after a `/` or at the start belongs to the path.
+            ("set-file /v/a=b.mp4", set_file(None, "/v/a=b.mp4")),
+            ("set-file =a.mp4", set_file(None, "=a.mp4")),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(Request::parse(line), Ok(expected), "{line}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("", "empty command"),
+            ("play", "unknown command `play`"),
+            ("pause now", "pause takes no argument"),
+            ("status all", "status takes no argument"),
+            ("set-file", "usage: set-file [NAME=]PATH"),
+            ("volume", "usage: volume 0.0–1.0"),
+            ("volume loud", "usage: volume 0.0–1.0"),
+            ("volume 1.5", "usage: volume 0.0–1.0"),
+            ("volume -0.1", "usage: volume 0.0–1.0"),
+            ("volume NaN", "usage: volume 0.0–1.0"),
+            ("fps 0", "usage: fps N | fps none"),
+            ("fps -30", "usage: fps N | fps none"),
+            ("fps 29.97", "usage: fps N | fps none"),
+            ("fps fast", "usage: fps N | fps none"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(Request::parse(line), Err(expected.to_owned()), "{line}");
+        }
+    }
+}
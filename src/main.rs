@@ -2,23 +2,64 @@
 // Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
 
 mod app;
+mod config;
 mod gpu_renderer;
 mod gst_pipeline;
+mod ipc;
+mod mpris;
+mod pause;
+mod playlist;
+mod power;
+mod rules;
+mod signals;
+mod wake;
 
+use std::collections::BTreeMap;
 use std::ffi::c_void;
 use std::os::fd::AsRawFd;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use clap::Parser;
-use wayland_client::{Connection, Proxy, globals::registry_queue_init};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use wayland_client::{
+    Connection, Proxy, QueueHandle,
+    globals::registry_queue_init,
+    protocol::{
+        wl_output::{Transform, WlOutput},
+        wl_seat::WlSeat,
+    },
+};
+use wayland_protocols::{
+    ext::idle_notify::v1::client::ext_idle_notifier_v1::ExtIdleNotifierV1,
+    wp::{
+        fractional_scale::v1::client::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+        presentation_time::client::wp_presentation::WpPresentation,
+        viewporter::client::wp_viewporter::WpViewporter,
+    },
+    xdg::xdg_output::zv1::client::zxdg_output_manager_v1::ZxdgOutputManagerV1,
+};
 use wayland_protocols_wlr::{
     foreign_toplevel::v1::client::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1,
     layer_shell::v1::client::zwlr_layer_shell_v1::ZwlrLayerShellV1,
+    output_power_management::v1::client::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
 };
 
-use app::State;
-use gpu_renderer::GpuRenderer;
-use gst_pipeline::Pipeline;
+use app::{Output, State};
+use config::{Config, OutputConfig};
+use gpu_renderer::{Filter, Gpu, GpuRenderer, ScaleMode, Transition};
+use gst_pipeline::{BusStatus, Pipeline, PipelineOptions};
+use ipc::{Event, Request};
+use pause::Reason;
+use playlist::Playlist;
+use power::Policies;
+use rules::{Effect, Rules};
+use signals::{Signal, Signals};
+use wake::Waker;
+
+/// An output that has not asked for a frame for this long is covered or on a
+/// hidden workspace; its video is paused until the compositor asks again.
+const HIDDEN_AFTER: Duration = Duration::from_secs(2);
 
 /// q6w — GStreamer video wallpaper for Wayland
 ///
@@ -30,12 +71,57 @@ use gst_pipeline::Pipeline;
 /// — no Vec allocation, no CPU copy.  VAAPI hardware decoding is used
 /// automatically when available; software fallback otherwise.
 #[derive(Parser, Debug)]
-#[command(name = "q6w", author, version = env!("FULL_VERSION"), about, long_about = None)]
+#[command(
+    name = "q6w",
+    author,
+    version = env!("FULL_VERSION"),
+    about,
+    long_about = None,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// Path to the video file
-    #[arg(short, long, value_name = "FILE", required_unless_present = "license")]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Read settings from FILE instead of $XDG_CONFIG_HOME/q6w/config.toml
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Apply the [profiles.NAME] section of the config file
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Path to the video file (played on every output without an --output mapping)
+    #[arg(short, long, value_name = "FILE")]
     file: Option<PathBuf>,
 
+    /// Play the videos in a directory or M3U playlist instead of a single file
+    #[arg(long, value_name = "DIR|M3U", conflicts_with = "file")]
+    playlist: Option<PathBuf>,
+
+    /// Play the playlist in random order
+    #[arg(long, requires = "playlist", conflicts_with = "file")]
+    shuffle: bool,
+
+    /// Switch to the next playlist video after this long (e.g. 90s, 10m, 1h)
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        requires = "playlist",
+        conflicts_with = "file"
+    )]
+    interval: Option<Duration>,
+
+    /// Move to the next playlist video when one ends instead of looping it
+    #[arg(long, requires = "playlist", conflicts_with = "file")]
+    advance_on_eos: bool,
+
+    /// Play FILE on the output with connector name NAME (e.g. DP-1=forest.mp4).
+    /// May be given once per monitor.
+    #[arg(long, value_name = "NAME=FILE", value_parser = parse_output_mapping)]
+    output: Vec<(String, PathBuf)>,
+
     /// Enable audio playback (disabled by default)
     #[arg(short, long)]
     audio: bool,
@@ -48,18 +134,55 @@ struct Args {
     #[arg(long)]
     mute_on_window: bool,
 
-    /// Pause video when any window is focused or maximized
+    /// Pause video while a window on its monitor is focused or maximized
     #[arg(long)]
     pause_on_window: bool,
 
-    /// Disable automatic pause when a window goes fullscreen
+    /// Keep playing on a monitor that has a fullscreen window
     #[arg(long)]
     no_pause_on_fullscreen: bool,
 
+    /// Where to read power supplies from; only useful for testing
+    #[arg(long, value_name = "DIR", default_value = power::SYSFS_ROOT, hide = true)]
+    power_supply_dir: PathBuf,
+
+    /// Free the decoder and video memory while a monitor is powered off;
+    /// the video starts over when it comes back on
+    #[arg(long)]
+    release_when_off: bool,
+
+    /// Pause video once there has been no input for this long (e.g. 5m)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    idle_timeout: Option<Duration>,
+
+    /// While idle, drop to this framerate instead of pausing
+    #[arg(long, value_name = "FPS", requires = "idle_timeout")]
+    idle_fps: Option<i32>,
+
     /// Target framerate limit (e.g. 30). Drops frames to hit the limit.
     #[arg(long, value_name = "FPS")]
     fps: Option<i32>,
 
+    /// How to fit videos whose aspect ratio differs from the monitor's
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = ScaleMode::Fill)]
+    scale_mode: ScaleMode,
+
+    /// Texture filter when scaling; `nearest` keeps pixel art crisp
+    #[arg(long, value_enum, value_name = "FILTER", default_value_t = Filter::Linear)]
+    filter: Filter,
+
+    /// Colour around the video in `fit` and `center` modes, as #RRGGBB
+    #[arg(long, value_name = "COLOR", default_value = "#000000", value_parser = parse_color)]
+    letterbox_color: [f32; 3],
+
+    /// Effect when switching to another video
+    #[arg(long, value_enum, value_name = "EFFECT", default_value_t = Transition::Fade)]
+    transition: Transition,
+
+    /// How long the transition between videos takes (e.g. 800ms, 2s)
+    #[arg(long, value_name = "DURATION", default_value = "1s", value_parser = parse_duration)]
+    transition_duration: Duration,
+
     /// Disable the software-fallback guard rail.
     ///
     /// By default, q6w refuses to software-decode videos larger than
@@ -73,6 +196,330 @@ struct Args {
     license: bool,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Control the q6w running on this Wayland display
+    Ctl {
+        #[command(subcommand)]
+        request: CtlRequest,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CtlRequest {
+    /// Pause playback on every output
+    Pause,
+    /// Resume playback paused with `pause`
+    Resume,
+    /// Play another video
+    SetFile {
+        /// Video file
+        file: PathBuf,
+        /// Only change the output with this connector name
+        #[arg(long, value_name = "NAME")]
+        output: Option<String>,
+    },
+    /// Set the audio volume, 0.0 – 1.0
+    Volume { volume: f32 },
+    /// Mute audio
+    Mute,
+    /// Unmute audio muted with `mute`
+    Unmute,
+    /// Skip to the next playlist video
+    Next,
+    /// Cap the framerate; without FPS, remove the cap
+    Fps { fps: Option<i32> },
+    /// Print playback state and the video on each output as JSON
+    Status,
+    /// Print a JSON line whenever playback state or a video changes
+    Subscribe,
+}
+
+impl CtlRequest {
+    /// The request line sent over the socket.
+    fn line(&self) -> String {
+        match self {
+            CtlRequest::Pause => "pause".into(),
+            CtlRequest::Resume => "resume".into(),
+            // Resolve here: the daemon's working directory is not ours.
+            CtlRequest::SetFile { file, output } => match output {
+                Some(name) => format!("set-file {name}={}", video_path(file)),
+                None => format!("set-file {}", video_path(file)),
+            },
+            CtlRequest::Volume { volume } => format!("volume {volume}"),
+            CtlRequest::Mute => "mute".into(),
+            CtlRequest::Unmute => "unmute".into(),
+            CtlRequest::Next => "next".into(),
+            CtlRequest::Fps { fps: Some(fps) } => format!("fps {fps}"),
+            CtlRequest::Fps { fps: None } => "fps none".into(),
+            CtlRequest::Status => "status".into(),
+            CtlRequest::Subscribe => "subscribe".into(),
+        }
+    }
+}
+
+/// Parse an `--output NAME=FILE` mapping.
+fn parse_output_mapping(s: &str) -> Result<(String, PathBuf), String> {
+    match s.split_once('=') {
+        Some((name, file)) if !name.is_empty() && !file.is_empty() => {
+            Ok((name.to_owned(), PathBuf::from(file)))
+        }
+        _ => Err(format!("expected NAME=FILE, got `{s}`")),
+    }
+}
+
+/// Parse a duration such as `500ms`, `90s`, `10m` or `1h`; a bare number
+/// is seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let n: u64 = num
+        .parse()
+        .map_err(|_| format!("expected a duration like 90s or 10m, got `{s}`"))?;
+    match unit.trim() {
+        "ms" => Ok(Duration::from_millis(n)),
+        "" | "s" => Ok(Duration::from_secs(n)),
+        "m" => Ok(Duration::from_secs(n * 60)),
+        "h" => Ok(Duration::from_secs(n * 3600)),
+        _ => Err(format!(
+            "unknown duration unit in `{s}` (use ms, s, m or h)"
+        )),
+    }
+}
+
+/// Parse a `#RRGGBB` colour into 0.0–1.0 RGB components.
+fn parse_color(s: &str) -> Result<[f32; 3], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!("expected #RRGGBB, got `{s}`"));
+    }
+    let mut rgb = [0.0; 3];
+    for (i, c) in rgb.iter_mut().enumerate() {
+        let byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("expected #RRGGBB, got `{s}`"))?;
+        *c = byte as f32 / 255.0;
+    }
+    Ok(rgb)
+}
+
+/// Resolve `file` to the absolute path handed to GStreamer.
+fn resolve_video(file: &Path) -> Result<String, String> {
+    if !file.exists() {
+        return Err(format!("file not found: {}", file.display()));
+    }
+    let abs_path = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+    Ok(abs_path.to_string_lossy().into_owned())
+}
+
+/// Like `resolve_video`, but exit if the file does not exist.
+fn video_path(file: &Path) -> String {
+    resolve_video(file).unwrap_or_else(|e| {
+        eprintln!("q6w: {e}");
+        std::process::exit(1);
+    })
+}
+
+/// Command line merged with the config file.
+struct Settings {
+    args: Args,
+    /// `[output.NAME]` overrides from the config file.
+    outputs: BTreeMap<String, OutputConfig>,
+    /// `[[rule]]` window rules from the config file.
+    rules: Rules,
+    /// `[[power]]` battery policies from the config file.
+    power: Policies,
+    /// The config file in use, or the one to watch for if it doesn't exist.
+    path: Option<PathBuf>,
+    /// The command line on its own, kept for reloading.
+    cli: clap::ArgMatches,
+}
+
+impl Settings {
+    /// Combine the command line `cli` with the config file: the file's
+    /// settings become arguments in front of the real ones.
+    fn load(cli: &clap::ArgMatches) -> Result<Self, String> {
+        let explicit = cli.get_one::<PathBuf>("config");
+        let path = explicit.cloned().or_else(config::default_path);
+        let profile = cli.get_one::<String>("profile").map(String::as_str);
+
+        let config = match &path {
+            Some(path) if explicit.is_some() || path.exists() => Some(
+                Config::load(path, profile, &Args::command(), cli)
+                    .map_err(|e| format!("{}: {e}", path.display()))?,
+            ),
+            _ if profile.is_some() => return Err("--profile needs a config file".into()),
+            _ => None,
+        };
+
+        let (config_args, outputs, rules, power) = match config {
+            Some(config) => (config.args, config.outputs, config.rules, config.power),
+            None => (
+                Vec::new(),
+                BTreeMap::new(),
+                Rules::default(),
+                Policies::default(),
+            ),
+        };
+        let argv = std::iter::once("q6w".into())
+            .chain(config_args)
+            .chain(std::env::args_os().skip(1));
+        // The command line parsed on its own, so any error here comes from
+        // the file.
+        let args = Args::try_parse_from(argv).map_err(|e| {
+            let msg = e.to_string();
+            let first = msg.lines().next().unwrap_or_default().to_owned();
+            match &path {
+                Some(path) => format!(
+                    "{}: {}",
+                    path.display(),
+                    first.trim_start_matches("error: ")
+                ),
+                None => first,
+            }
+        })?;
+
+        if args.file.is_none() && args.playlist.is_none() && args.output.is_empty() {
+            return Err("nothing to play: pass --file, --playlist or --output, \
+                 or set one in the config file"
+                .into());
+        }
+        Ok(Settings {
+            args,
+            outputs,
+            rules,
+            power,
+            path,
+            cli: cli.clone(),
+        })
+    }
+
+    /// Playback options for every output before per-output overrides.
+    fn pipeline_options(&self) -> PipelineOptions {
+        PipelineOptions {
+            enable_audio: self.args.audio,
+            volume: self.args.volume.clamp(0.0, 1.0) as f64,
+            fps: self.args.fps,
+            scale_mode: self.args.scale_mode,
+            filter: self.args.filter,
+        }
+    }
+
+    /// What the activated or maximized windows on output `name` do to the
+    /// wallpaper, or those on any output with `None`.
+    fn window_effect(&self, state: &State, name: Option<u32>) -> Effect {
+        let fallback = Effect {
+            pause: self.args.pause_on_window,
+            mute: self.args.mute_on_window,
+            ..Effect::default()
+        };
+        state
+            .windows()
+            .filter(|w| w.active && name.is_none_or(|name| w.is_on(name)))
+            .map(|w| self.rules.effect(&w.app_id, &w.title).unwrap_or(fallback))
+            .fold(Effect::default(), Effect::combine)
+    }
+
+    /// What the `[[power]]` policies do right now.
+    fn battery_action(&self, monitor: Option<&power::Monitor>) -> power::Action {
+        monitor
+            .map(|m| self.power.action(m.supply()))
+            .unwrap_or_default()
+    }
+
+    /// Options for `output` with its `[output.NAME]` section applied, and
+    /// its letterbox colour.
+    fn output_options(
+        &self,
+        options: &PipelineOptions,
+        output: &Output,
+    ) -> (PipelineOptions, [f32; 3]) {
+        let section = output.name.as_ref().and_then(|name| self.outputs.get(name));
+        let Some(section) = section else {
+            return (*options, self.args.letterbox_color);
+        };
+        let options = PipelineOptions {
+            fps: section.fps.or(options.fps),
+            scale_mode: section.scale_mode.unwrap_or(options.scale_mode),
+            filter: section.filter.unwrap_or(options.filter),
+            ..*options
+        };
+        (
+            options,
+            section.letterbox_color.unwrap_or(self.args.letterbox_color),
+        )
+    }
+}
+
+/// Which video each output plays.
+struct Videos {
+    /// `--output NAME=FILE` mappings, paths already resolved.
+    mappings: Vec<(String, String)>,
+    /// `--file`, used on every output without a mapping.
+    default: Option<String>,
+    /// `--playlist`, used instead of `--file`.
+    playlist: Option<Playlist>,
+}
+
+impl Videos {
+    fn new(args: &Args) -> Result<Self, String> {
+        // Later mappings win, so the command line overrides the config file.
+        let mut mappings: Vec<(String, String)> = Vec::new();
+        for (name, file) in &args.output {
+            let path = resolve_video(file)?;
+            match mappings.iter_mut().find(|(n, _)| n == name) {
+                Some(mapping) => mapping.1 = path,
+                None => mappings.push((name.clone(), path)),
+            }
+        }
+        Ok(Videos {
+            mappings,
+            default: args.file.as_deref().map(resolve_video).transpose()?,
+            playlist: args
+                .playlist
+                .as_deref()
+                .map(|path| Playlist::load(path, args.shuffle))
+                .transpose()
+                .map_err(|e| format!("playlist: {e}"))?,
+        })
+    }
+
+    fn mapping(&self, output: &Output) -> Option<&str> {
+        let name = output.name.as_deref()?;
+        self.mappings
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, path)| path.as_str())
+    }
+
+    /// Pick the video for `output`: a mapping for its connector name wins
+    /// over the default file or the playlist's current video.
+    fn for_output(&self, output: &Output) -> Option<&str> {
+        self.mapping(output)
+            .or(self.default.as_deref())
+            .or(self.playlist.as_ref().map(Playlist::current))
+    }
+
+    /// Play `path` on the output called `output`, or on every output.
+    fn set_file(&mut self, output: Option<&str>, path: String) {
+        match output {
+            Some(name) => match self.mappings.iter_mut().find(|(n, _)| n == name) {
+                Some(mapping) => mapping.1 = path,
+                None => self.mappings.push((name.to_owned(), path)),
+            },
+            None => {
+                self.mappings.clear();
+                self.default = Some(path);
+                self.playlist = None;
+            }
+        }
+    }
+
+    /// Whether `output` shows the playlist rather than a fixed file.
+    fn follows_playlist(&self, output: &Output) -> bool {
+        self.playlist.is_some() && self.mapping(output).is_none()
+    }
+}
+
 /// Return the raw `wl_display *` C pointer.
 ///
 /// On Linux, wayland-client uses the system (libwayland-client.so) backend
@@ -83,8 +530,8 @@ fn display_ptr(conn: &Connection) -> *mut c_void {
 }
 
 /// Return the raw `wl_surface *` C pointer for use with wgpu.
-fn surface_ptr(state: &State) -> *mut c_void {
-    state
+fn surface_ptr(output: &Output) -> *mut c_void {
+    output
         .surface
         .as_ref()
         .expect("surface not yet created")
@@ -93,8 +540,557 @@ fn surface_ptr(state: &State) -> *mut c_void {
         .cast()
 }
 
+/// Create layer surfaces for outputs that have a video but no surface yet,
+/// both at startup and when a monitor is plugged in.
+fn create_missing_surfaces(state: &mut State, qh: &QueueHandle<State>, videos: &Videos) {
+    let names: Vec<u32> = state
+        .outputs
+        .iter()
+        .filter(|(_, o)| o.ready && !o.closed && !o.removed && o.surface.is_none())
+        .filter(|(_, o)| videos.for_output(o).is_some())
+        .map(|(&name, _)| name)
+        .collect();
+    for name in names {
+        state.create_layer_surface(qh, name);
+    }
+}
+
+/// Tear down wallpapers whose monitor was unplugged or whose layer surface
+/// the compositor closed.
+fn reap_outputs(
+    state: &mut State,
+    wallpapers: &mut BTreeMap<u32, Wallpaper>,
+    audio_owner: &mut Option<u32>,
+) {
+    let gone: Vec<u32> = state
+        .outputs
+        .iter()
+        .filter(|(_, o)| o.removed || (o.closed && o.surface.is_some()))
+        .map(|(&name, _)| name)
+        .collect();
+    for name in gone {
+        // Drop the renderer before its surface goes away.
+        wallpapers.remove(&name);
+        if *audio_owner == Some(name) {
+            *audio_owner = None;
+        }
+        state.destroy_layer_surface(name);
+        if state.outputs[&name].removed {
+            state.remove_output(name);
+        }
+    }
+}
+
+/// Create the renderer and decode pipeline for a configured output, on the
+/// GPU device the other outputs use, if any.
+///
+/// Returns `None`, after explaining why, if the GPU renderer cannot be
+/// created or the software-decoding guard rejects the output size.
+#[allow(clippy::too_many_arguments)]
+fn spawn_wallpaper(
+    conn: &Connection,
+    gpu: Option<Arc<Gpu>>,
+    output: &Output,
+    path: &str,
+    options: &PipelineOptions,
+    letterbox: [f32; 3],
+    args: &Args,
+    waker: &Waker,
+) -> Option<Wallpaper> {
+    let (buf_w, buf_h) = output.buffer_size();
+    let (frame_w, frame_h) = output.frame_size();
+
+    // Zero-copy path: GstBuffer → write_texture → GPU → present
+    let renderer = unsafe {
+        GpuRenderer::new(
+            gpu,
+            display_ptr(conn),
+            surface_ptr(output),
+            buf_w as u32,
+            buf_h as u32,
+        )
+    };
+    let mut renderer = match renderer {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("q6w: failed to create GPU renderer — check Vulkan drivers: {e}");
+            return None;
+        }
+    };
+
+    let pipeline = Pipeline::new(path, options, frame_w, frame_h, waker);
+
+    // Without VAAPI, hi-res decoding can saturate CPU and consume GB of RAM.
+    if pipeline.is_software_fallback() {
+        let pixels = (frame_w as u64) * (frame_h as u64);
+        let is_high_res = pixels > 1920 * 1080; // anything above Full HD
+
+        if is_high_res && !args.no_fallback_guard {
+            eprintln!();
+            eprintln!(
+                "q6w: Software decoding at {}×{} is not recommended.",
+                frame_w, frame_h
+            );
+            eprintln!("q6w: Without VAAPI, high-resolution decode will cause excessive CPU");
+            eprintln!("q6w: and memory usage. Consider downscaling the video or installing");
+            eprintln!("q6w: the appropriate VA-API driver for your GPU.");
+            eprintln!();
+            eprintln!("q6w: To proceed anyway, re-run with --no-fallback-guard.");
+            return None;
+        }
+    }
+
+    renderer.set_transform(output.transform);
+    renderer.set_scaling(options.scale_mode, options.filter, letterbox);
+    output.apply_buffer_geometry();
+
+    Some(Wallpaper {
+        renderer,
+        pipeline,
+        incoming: None,
+        path: path.to_owned(),
+        options: *options,
+        transition: args.transition,
+        transition_len: args.transition_duration,
+        looping: true,
+        pause: pause::Controller::default(),
+        muted: false,
+        hidden: false,
+        halted: false,
+        stopped: false,
+        still: false,
+        released: false,
+        release_when_off: args.release_when_off,
+        refresh: None,
+        due: None,
+        dim: 0.0,
+        fps_cap: None,
+        redraw: false,
+        width: output.width,
+        height: output.height,
+        scale: output.scale,
+        preferred_scale: output.preferred_scale,
+        transform: output.transform,
+        waker: waker.clone(),
+    })
+}
+
+/// Everything that draws the video onto one output.
+///
+/// The renderer presents onto the output's `wl_surface`, which `State` owns:
+/// a wallpaper must be dropped before that surface is destroyed (see
+/// `reap_outputs`) and before the Wayland connection closes, which is why
+/// `main` declares `wallpapers` after `conn` and `state`.  The GPU device is
+/// shared and outlives whichever renderer drops last.
+///
+/// Each output decodes on its own pipeline even when outputs show the same
+/// file: the frames are scaled to the output's size, and pausing, frame-rate
+/// caps and stopping apply per output.
+struct Wallpaper {
+    renderer: GpuRenderer,
+    pipeline: Pipeline,
+    /// Next video, prerolling until its first frame is ready to transition to.
+    incoming: Option<(Pipeline, String)>,
+    /// Video currently loaded in `pipeline`.
+    path: String,
+    options: PipelineOptions,
+    transition: Transition,
+    transition_len: Duration,
+    /// Playback state, so a pipeline built for the next video matches.
+    looping: bool,
+    pause: pause::Controller,
+    muted: bool,
+    /// The output stopped asking for frames.
+    hidden: bool,
+    /// Whether the pipelines are actually paused.
+    halted: bool,
+    /// Whether the pipelines are stopped, i.e. in NULL.
+    stopped: bool,
+    /// Stop instead of pausing for a power policy.
+    still: bool,
+    /// Whether the textures are freed, while the monitor is off and
+    /// `release_when_off` is set.
+    released: bool,
+    release_when_off: bool,
+    /// Refresh period the pipelines' frame lead was set for.
+    refresh: Option<Duration>,
+    /// When the next decoded frame is due on screen, if one is held back.
+    due: Option<Instant>,
+    /// Window rule actions currently applied.
+    dim: f32,
+    fps_cap: Option<i32>,
+    /// Draw even without a new frame, e.g. after dimming while paused.
+    redraw: bool,
+    /// Output geometry the renderer and pipeline were last sized for.
+    width: i32,
+    height: i32,
+    scale: i32,
+    preferred_scale: Option<u32>,
+    transform: Transform,
+    /// Handed to the pipelines so new frames wake the main loop.
+    waker: Waker,
+}
+
+impl Wallpaper {
+    /// Follow layer-surface reconfigures (mode change, rotation, layout) and
+    /// output scale changes by resizing the swapchain and the decoded frames.
+    fn sync_geometry(&mut self, output: &Output) {
+        let current = (
+            output.width,
+            output.height,
+            output.scale,
+            output.preferred_scale,
+            output.transform,
+        );
+        let applied = (
+            self.width,
+            self.height,
+            self.scale,
+            self.preferred_scale,
+            self.transform,
+        );
+        if current == applied {
+            return;
+        }
+        self.width = output.width;
+        self.height = output.height;
+        self.scale = output.scale;
+        self.preferred_scale = output.preferred_scale;
+        self.transform = output.transform;
+
+        output.apply_buffer_geometry();
+        let (buf_w, buf_h) = output.buffer_size();
+        let (frame_w, frame_h) = output.frame_size();
+        self.renderer.resize(buf_w as u32, buf_h as u32);
+        self.renderer.set_transform(output.transform);
+        self.pipeline.set_size(frame_w, frame_h);
+        if let Some((incoming, _)) = &self.incoming {
+            incoming.set_size(frame_w, frame_h);
+        }
+    }
+
+    /// The video being shown, or about to be once the switch completes.
+    fn target(&self) -> &str {
+        self.incoming.as_ref().map_or(&self.path, |(_, path)| path)
+    }
+
+    /// The pipelines currently decoding: the one on screen and, during a
+    /// switch, the incoming one.
+    fn pipelines(&self) -> impl Iterator<Item = &Pipeline> {
+        std::iter::once(&self.pipeline).chain(self.incoming.as_ref().map(|(p, _)| p))
+    }
+
+    /// Start playing, unless `apply_pause` already paused it.
+    fn start(&self) {
+        if !self.halted {
+            self.pipelines().for_each(Pipeline::play);
+        }
+    }
+
+    fn mute(&mut self) {
+        self.muted = true;
+        self.pipelines().for_each(Pipeline::mute);
+    }
+
+    fn unmute(&mut self) {
+        self.muted = false;
+        self.pipelines().for_each(Pipeline::unmute);
+    }
+
+    /// Whether this output's pipeline is the one playing sound right now.
+    fn audible(&self) -> bool {
+        self.options.enable_audio && !self.muted
+    }
+
+    /// Pause, stop or resume the pipelines to match the pause reasons.
+    fn apply_pause(&mut self) {
+        let halt = self.pause.active();
+        let release = self.release_when_off && self.pause.contains(Reason::PowerOff);
+        let stop = release || (self.still && self.pause.contains(Reason::Battery));
+        if (halt, stop, release) == (self.halted, self.stopped, self.released) {
+            return;
+        }
+        if release && !self.released {
+            self.renderer.release_frames();
+        }
+        if stop {
+            if !self.stopped {
+                self.pipelines().for_each(Pipeline::stop);
+            }
+        } else if halt {
+            self.pipelines().for_each(Pipeline::pause);
+        } else {
+            self.pipelines().for_each(Pipeline::resume);
+        }
+        self.halted = halt;
+        self.stopped = stop;
+        self.released = release;
+    }
+
+    fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+        self.pipelines().for_each(|p| p.set_looping(looping));
+    }
+
+    fn set_volume(&mut self, volume: f64) {
+        self.options.volume = volume;
+        self.pipelines().for_each(|p| p.set_volume(volume));
+    }
+
+    /// Set the configured framerate limit: `--fps`, `[output.NAME]` or
+    /// `q6w ctl fps`, whichever came last.
+    fn set_fps(&mut self, fps: Option<i32>) {
+        self.options.fps = fps;
+        let fps = self.fps();
+        self.pipelines().for_each(|p| p.set_fps(fps));
+    }
+
+    /// Limit the framerate further while a window rule, idleness or a
+    /// battery policy asks for it.
+    fn set_fps_cap(&mut self, cap: Option<i32>) {
+        if cap != self.fps_cap {
+            self.fps_cap = cap;
+            let fps = self.fps();
+            self.pipelines().for_each(|p| p.set_fps(fps));
+        }
+    }
+
+    /// The framerate limit in effect: the configured one or a lower cap.
+    fn fps(&self) -> Option<i32> {
+        match (self.options.fps, self.fps_cap) {
+            (Some(fps), Some(cap)) => Some(fps.min(cap)),
+            (fps, cap) => fps.or(cap),
+        }
+    }
+
+    fn set_dim(&mut self, dim: f32) {
+        if dim != self.dim {
+            self.dim = dim;
+            self.renderer.set_dim(dim);
+            self.redraw = true;
+        }
+    }
+
+    fn set_scaling(&mut self, scale_mode: ScaleMode, filter: Filter, letterbox: [f32; 3]) {
+        self.options.scale_mode = scale_mode;
+        self.options.filter = filter;
+        self.renderer.set_scaling(scale_mode, filter, letterbox);
+        self.pipelines()
+            .for_each(|p| p.set_scaling(scale_mode, filter));
+    }
+
+    /// Switch to another video.  Without a transition the current pipeline
+    /// just loads the new file; otherwise a second pipeline prerolls it in
+    /// the background and takes over once its first frame is decoded.
+    fn switch_to(&mut self, path: &str, output: &Output) {
+        if self.transition == Transition::None || self.transition_len.is_zero() {
+            self.incoming = None;
+            self.pipeline.set_file(path);
+            self.path = path.to_owned();
+            return;
+        }
+        let (frame_w, frame_h) = output.frame_size();
+        let options = PipelineOptions {
+            fps: self.fps(),
+            ..self.options
+        };
+        let pipeline = Pipeline::new(path, &options, frame_w, frame_h, &self.waker);
+        pipeline.set_looping(self.looping);
+        pipeline.set_refresh(self.refresh);
+        if self.muted {
+            pipeline.mute();
+        }
+        if self.stopped {
+            // Stays in NULL until playback resumes.
+        } else if self.halted {
+            pipeline.pause();
+        } else {
+            pipeline.play();
+        }
+        // Replacing an earlier incoming pipeline drops it unseen.
+        self.incoming = Some((pipeline, path.to_owned()));
+    }
+
+    /// Render the decoded frame due next, if any and if the compositor wants
+    /// one, and process bus messages.
+    ///
+    /// An incoming pipeline replaces the current one as soon as it delivers a
+    /// frame, which starts the transition.  While a transition runs, every
+    /// frame callback draws so it animates even without new frames.
+    /// Undrawn frames stay in the appsink, which keeps only the newest.
+    fn present(&mut self, output: &mut Output, name: u32, qh: &QueueHandle<State>) -> BusStatus {
+        let failed = self
+            .incoming
+            .as_ref()
+            .is_some_and(|(incoming, _)| matches!(incoming.handle_bus(), BusStatus::Fatal));
+        if failed {
+            let (_, path) = self.incoming.take().unwrap();
+            eprintln!("q6w: cannot switch to {path}, keeping the current video");
+        }
+        if output.refresh != self.refresh {
+            self.refresh = output.refresh;
+            self.pipelines().for_each(|p| p.set_refresh(output.refresh));
+        }
+        if !output.wants_frame() {
+            return self.pipeline.handle_bus();
+        }
+        let mut switched = false;
+        if let Some((incoming, _)) = &self.incoming {
+            let renderer = &mut self.renderer;
+            let (transition, duration) = (self.transition, self.transition_len);
+            incoming.with_latest_frame(|data, w, h| {
+                switched = renderer.upload_first(data, w as u32, h as u32, transition, duration);
+            });
+        }
+        if switched {
+            let (pipeline, path) = self.incoming.take().unwrap();
+            // The old pipeline is dropped here → set_state(Null).
+            self.pipeline = pipeline;
+            self.path = path;
+        }
+        let mut uploaded = switched;
+        if !switched {
+            // Show the frame whose time is closest to the vblank this
+            // commit will land on.
+            let delay = output.until_vblank();
+            let slack = output.refresh.unwrap_or_default() / 2;
+            let renderer = &mut self.renderer;
+            let next = self.pipeline.with_frame_due(delay, slack, |data, w, h| {
+                uploaded = renderer.upload(data, w as u32, h as u32);
+            });
+            self.due = next.map(|d| Instant::now() + d);
+        }
+        if uploaded || self.renderer.in_transition() || self.redraw {
+            self.redraw = false;
+            output.request_frame(qh, name);
+            // Commit anyway so the callback isn't left waiting on a frame
+            // that never comes.
+            if !self.renderer.render()
+                && let Some(surface) = &output.surface
+            {
+                surface.commit();
+            }
+        }
+        self.pipeline.handle_bus()
+    }
+}
+
+/// Re-read the config file and apply what changed to the running
+/// wallpapers; with `restart`, reopen every video even if its path stayed
+/// the same.  On any error the current settings stay in effect.
+fn reload(
+    settings: &mut Settings,
+    restart: bool,
+    videos: &mut Videos,
+    options: &mut PipelineOptions,
+    wallpapers: &mut BTreeMap<u32, Wallpaper>,
+    outputs: &BTreeMap<u32, Output>,
+    ipc: &mut Option<ipc::Server>,
+) {
+    let loaded = Settings::load(&settings.cli).and_then(|new| {
+        let mut new_videos = Videos::new(&new.args)?;
+        // Keep the playlist's position when it is the same list.
+        if (&new.args.playlist, new.args.shuffle)
+            == (&settings.args.playlist, settings.args.shuffle)
+        {
+            new_videos.playlist = videos.playlist.take();
+        }
+        Ok((new, new_videos))
+    });
+    let (mut new, new_videos) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("q6w: config not reloaded: {e}");
+            return;
+        }
+    };
+    if new.args.audio != settings.args.audio {
+        eprintln!("q6w: turning audio on or off needs a restart");
+        new.args.audio = settings.args.audio;
+    }
+    *settings = new;
+    *videos = new_videos;
+    *options = settings.pipeline_options();
+
+    for (name, wp) in wallpapers.iter_mut() {
+        let output = &outputs[name];
+        let (opts, letterbox) = settings.output_options(options, output);
+        wp.set_scaling(opts.scale_mode, opts.filter, letterbox);
+        if wp.options.fps != opts.fps {
+            wp.set_fps(opts.fps);
+        }
+        if wp.options.volume != opts.volume {
+            wp.set_volume(opts.volume);
+        }
+        wp.transition = settings.args.transition;
+        wp.release_when_off = settings.args.release_when_off;
+        wp.transition_len = settings.args.transition_duration;
+        wp.set_looping(!(settings.args.advance_on_eos && videos.follows_playlist(output)));
+        if let Some(path) = videos.for_output(output)
+            && (restart || path != wp.target())
+        {
+            wp.switch_to(path, output);
+            notify(
+                ipc,
+                Event::Video {
+                    output: output.name.as_deref().unwrap_or_default(),
+                    file: path,
+                },
+            );
+        }
+    }
+    eprintln!("q6w: config reloaded");
+}
+
+/// Bring the pause reasons of the wallpaper on `output` up to date and
+/// pause or resume it.  Returns `true` if the reason to report changed.
+///
+/// A hidden output keeps playing while it is the one making sound; with
+/// `--idle-fps`, idleness only lowers the framerate.
+fn update_pause(
+    wp: &mut Wallpaper,
+    user_paused: bool,
+    args: &Args,
+    output: &Output,
+    windows: &Effect,
+    idle: bool,
+    battery: power::Action,
+) -> bool {
+    let fullscreen = !args.no_pause_on_fullscreen && output.fullscreen_count > 0;
+    let hidden = wp.hidden && !wp.audible();
+    let mut changed = wp.pause.set(Reason::User, user_paused);
+    changed |= wp.pause.set(Reason::PowerOff, output.powered_off);
+    changed |= wp.pause.set(Reason::Fullscreen, fullscreen);
+    changed |= wp.pause.set(Reason::Window, windows.pause);
+    changed |= wp.pause.set(Reason::Idle, idle && args.idle_fps.is_none());
+    changed |= wp.pause.set(Reason::Battery, battery.halts());
+    wp.still = battery.still;
+    changed |= wp.pause.set(Reason::Hidden, hidden);
+    wp.apply_pause();
+    changed
+}
+
+/// `poll()` timeout for `d`, rounded up so the deadline has passed on wakeup.
+fn poll_millis(d: Duration) -> libc::c_int {
+    d.as_nanos()
+        .div_ceil(1_000_000)
+        .min(libc::c_int::MAX as u128) as libc::c_int
+}
+
+/// Push `event` to IPC subscribers, if the socket is up.
+fn notify(ipc: &mut Option<ipc::Server>, event: Event) {
+    if let Some(ipc) = ipc {
+        ipc.broadcast(&event);
+    }
+}
+
 fn main() {
-    let args = Args::parse();
+    let cli = Args::command().get_matches();
+    let args = Args::from_arg_matches(&cli).unwrap_or_else(|e| e.exit());
+
+    if let Some(Command::Ctl { request }) = &args.command {
+        std::process::exit(ipc::run_client(&request.line()));
+    }
 
     if args.license {
         println!(
@@ -107,19 +1103,41 @@ fn main() {
         std::process::exit(0);
     }
 
-    let file = args
-        .file
-        .expect("--file is required when --license is not used");
+    // Before anything starts a thread, so every thread inherits the mask.
+    let signals = Signals::new().unwrap_or_else(|e| {
+        eprintln!("q6w: cannot set up signal handling: {e}");
+        std::process::exit(1);
+    });
 
-    if !file.exists() {
-        eprintln!("q6w: file not found: {}", file.display());
+    let waker = Waker::new().unwrap_or_else(|e| {
+        eprintln!("q6w: cannot create the main loop eventfd: {e}");
         std::process::exit(1);
-    }
+    });
 
-    let abs_path = file.canonicalize().unwrap_or_else(|_| file.clone());
-    let path_str = abs_path.to_string_lossy().into_owned();
-    let enable_audio = args.audio;
-    let volume = args.volume.clamp(0.0, 1.0) as f64;
+    let mut settings = Settings::load(&cli).unwrap_or_else(|e| {
+        eprintln!("q6w: {e}");
+        std::process::exit(1);
+    });
+    let mut videos = Videos::new(&settings.args).unwrap_or_else(|e| {
+        eprintln!("q6w: {e}");
+        std::process::exit(1);
+    });
+    let enable_audio = settings.args.audio;
+    let mut options = settings.pipeline_options();
+
+    // Only a config directory that exists can be watched; create it and
+    // restart to have a new config file picked up.
+    let watcher = settings
+        .path
+        .as_deref()
+        .filter(|path| path.parent().is_some_and(Path::exists))
+        .and_then(|path| match config::Watcher::new(path) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                eprintln!("q6w: not watching the config file: {e}");
+                None
+            }
+        });
 
     let conn = Connection::connect_to_env()
         .expect("q6w: cannot connect to Wayland — is WAYLAND_DISPLAY set?");
@@ -137,6 +1155,19 @@ fn main() {
     state.toplevel_mgr = globals
         .bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ())
         .ok();
+    state.xdg_output_mgr = globals
+        .bind::<ZxdgOutputManagerV1, _, _>(&qh, 2..=3, ())
+        .ok();
+    state.fractional_scale_mgr = globals
+        .bind::<WpFractionalScaleManagerV1, _, _>(&qh, 1..=1, ())
+        .ok();
+    state.output_power_mgr = globals
+        .bind::<ZwlrOutputPowerManagerV1, _, _>(&qh, 1..=1, ())
+        .ok();
+    state.viewporter = globals.bind::<WpViewporter, _, _>(&qh, 1..=1, ()).ok();
+    state.presentation = globals.bind::<WpPresentation, _, _>(&qh, 1..=1, ()).ok();
+    state.seat = globals.bind::<WlSeat, _, _>(&qh, 1..=1, ()).ok();
+    state.idle_notifier = globals.bind::<ExtIdleNotifierV1, _, _>(&qh, 1..=1, ()).ok();
 
     if state.compositor.is_none() {
         eprintln!("q6w: wl_compositor not found");
@@ -153,111 +1184,435 @@ fn main() {
             "q6w: zwlr_foreign_toplevel_management_v1 not available — pause-on-fullscreen disabled"
         );
     }
+    if state.idle_notifier.is_none() && settings.args.idle_timeout.is_some() {
+        eprintln!("q6w: ext_idle_notifier_v1 not available — idle-timeout disabled");
+    }
+    state.watch_idle(settings.args.idle_timeout, &qh);
 
-    if !state.create_layer_surface(&qh) {
-        std::process::exit(1);
+    for global in globals.contents().clone_list() {
+        if global.interface == WlOutput::interface().name {
+            state.add_output(globals.registry(), global.name, global.version, &qh);
+        }
     }
 
+    // Receive connector names before deciding which outputs get a wallpaper.
     queue
         .roundtrip(&mut state)
         .expect("Wayland roundtrip failed");
 
-    if !state.configured {
-        eprintln!("q6w: layer-surface configure event not received — aborting");
-        std::process::exit(1);
+    for (name, _) in &videos.mappings {
+        if !state
+            .outputs
+            .values()
+            .any(|o| o.name.as_ref() == Some(name))
+        {
+            eprintln!("q6w: --output {name}: not connected — waiting for it");
+        }
     }
 
-    // Created after configure to use exact monitor dimensions.
-    // Zero-copy path: GstBuffer → write_texture → GPU → present
-    let renderer = unsafe {
-        GpuRenderer::new(
-            display_ptr(&conn),
-            surface_ptr(&state),
-            state.buf_w as u32,
-            state.buf_h as u32,
-        )
-        .expect("q6w: failed to create GPU renderer — check Vulkan drivers")
-    };
+    create_missing_surfaces(&mut state, &qh, &videos);
+    if state.outputs.values().all(|o| o.surface.is_none()) {
+        let available: Vec<&str> = state
+            .outputs
+            .values()
+            .filter_map(|o| o.name.as_deref())
+            .collect();
+        eprintln!(
+            "q6w: no output to draw on yet (connected: {}) — waiting for a monitor",
+            available.join(", ")
+        );
+    }
 
-    let pipeline = Pipeline::new(
-        &path_str,
-        enable_audio,
-        volume,
-        state.buf_w,
-        state.buf_h,
-        args.fps,
-    );
+    queue
+        .roundtrip(&mut state)
+        .expect("Wayland roundtrip failed");
 
-    // Without VAAPI, hi-res decoding can saturate CPU and consume GB of RAM.
-    if pipeline.is_software_fallback() {
-        let pixels = (state.buf_w as u64) * (state.buf_h as u64);
-        let is_high_res = pixels > 1920 * 1080; // anything above Full HD
+    if state
+        .outputs
+        .values()
+        .any(|o| o.surface.is_some() && !o.configured)
+    {
+        eprintln!("q6w: layer-surface configure event not received — aborting");
+        std::process::exit(1);
+    }
 
-        if is_high_res && !args.no_fallback_guard {
-            eprintln!();
-            eprintln!(
-                "q6w: Software decoding at {}×{} is not recommended.",
-                state.buf_w, state.buf_h
-            );
-            eprintln!("q6w: Without VAAPI, high-resolution decode will cause excessive CPU");
-            eprintln!("q6w: and memory usage. Consider downscaling the video or installing");
-            eprintln!("q6w: the appropriate VA-API driver for your GPU.");
-            eprintln!();
-            eprintln!("q6w: To proceed anyway, re-run with --no-fallback-guard.");
-            std::process::exit(1);
+    let mut wallpapers: BTreeMap<u32, Wallpaper> = BTreeMap::new();
+    // The output whose pipeline plays sound; the others would just echo it.
+    let mut audio_owner: Option<u32> = None;
+    let mut startup = true;
+    let mut last_switch = Instant::now();
+
+    let mut ipc = match ipc::Server::bind() {
+        Ok(server) => Some(server),
+        Err(e) => {
+            eprintln!("q6w: IPC disabled: {e}");
+            None
         }
-    }
+    };
 
-    pipeline.play();
+    // The player media keys and playerctl talk to; only useful with sound.
+    let mpris = if enable_audio {
+        match mpris::Mpris::new(options.volume, &waker) {
+            Ok(mpris) => Some(mpris),
+            Err(e) => {
+                eprintln!("q6w: MPRIS disabled: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut skip_requested = false;
 
-    let mut was_paused_fs = false;
-    let mut was_paused_window = false;
-    let mut was_muted = false;
+    // Pause/mute requested over IPC, MPRIS or signals, and why playback is
+    // currently muted.  Pausing is per output.
+    let mut user_paused = false;
+    let mut user_muted = false;
+    let mut mute = pause::Controller::default();
+    // Reads the batteries while there are `[[power]]` policies.
+    let mut power_monitor: Option<power::Monitor> = None;
 
     loop {
-        pipeline.with_latest_frame(|data, _w, _h| renderer.upload_and_render(data));
+        // Reap first so nothing renders onto an unplugged output's surface.
+        reap_outputs(&mut state, &mut wallpapers, &mut audio_owner);
+        create_missing_surfaces(&mut state, &qh, &videos);
 
-        if pipeline.handle_bus() {
-            break;
+        // One renderer and one pipeline per output, each created after
+        // configure to use that monitor's exact dimensions.  Monitors plugged
+        // in at runtime come through here too.
+        let pending: Vec<u32> = state
+            .outputs
+            .iter()
+            .filter(|(_, o)| o.configured && !o.closed && !o.removed)
+            .filter(|(name, _)| !wallpapers.contains_key(name))
+            .map(|(&name, _)| name)
+            .collect();
+        for name in pending {
+            let output = &state.outputs[&name];
+            let Some(path) = videos.for_output(output) else {
+                continue;
+            };
+            let audio = enable_audio && audio_owner.is_none();
+            let (output_options, letterbox) = settings.output_options(&options, output);
+            let options = PipelineOptions {
+                enable_audio: audio,
+                ..output_options
+            };
+            let gpu = wallpapers
+                .values()
+                .next()
+                .map(|wp| Arc::clone(wp.renderer.gpu()));
+            match spawn_wallpaper(
+                &conn,
+                gpu,
+                output,
+                path,
+                &options,
+                letterbox,
+                &settings.args,
+                &waker,
+            ) {
+                Some(mut wp) => {
+                    if audio {
+                        audio_owner = Some(name);
+                    }
+                    wp.set_looping(
+                        !(settings.args.advance_on_eos && videos.follows_playlist(output)),
+                    );
+                    if mute.active() {
+                        wp.mute();
+                    }
+                    let effect = settings.window_effect(&state, Some(name));
+                    update_pause(
+                        &mut wp,
+                        user_paused,
+                        &settings.args,
+                        output,
+                        &effect,
+                        state.idle,
+                        settings.battery_action(power_monitor.as_ref()),
+                    );
+                    wp.start();
+                    wallpapers.insert(name, wp);
+                }
+                None if startup => std::process::exit(1),
+                // Don't retry every iteration; the surface is reaped below.
+                None => {
+                    if let Some(output) = state.outputs.get_mut(&name) {
+                        output.closed = true;
+                    }
+                }
+            }
+        }
+        startup = false;
+
+        for (name, wp) in wallpapers.iter_mut() {
+            wp.sync_geometry(&state.outputs[name]);
         }
 
-        if !args.no_pause_on_fullscreen {
-            if state.paused_for_fs != was_paused_fs {
-                was_paused_fs = state.paused_for_fs;
-                if was_paused_fs {
-                    pipeline.pause();
-                } else if !state.paused_for_windows {
-                    pipeline.resume();
+        let mut fatal = false;
+        let mut finished = false;
+        for (&name, wp) in wallpapers.iter_mut() {
+            let output = state.outputs.get_mut(&name).expect("reaped above");
+            match wp.present(output, name, &qh) {
+                BusStatus::Ok => {}
+                // Only a playlist video ending advances the playlist, and not
+                // while the switch away from it is under way.
+                BusStatus::Finished => {
+                    finished |= wp.incoming.is_none() && videos.follows_playlist(output);
                 }
+                BusStatus::Fatal => fatal = true,
             }
+            // No frame callback for a while: the surface isn't being shown.
+            wp.hidden = output
+                .frame_requested
+                .is_some_and(|t| t.elapsed() >= HIDDEN_AFTER);
+        }
+        if fatal {
+            break;
         }
 
-        if args.pause_on_window {
-            if state.paused_for_windows != was_paused_window {
-                was_paused_window = state.paused_for_windows;
-                if was_paused_window {
-                    pipeline.pause();
-                } else if !state.paused_for_fs || args.no_pause_on_fullscreen {
-                    pipeline.resume();
+        // Playlist rotation: on the interval, or when a video ended with
+        // --advance-on-eos.  Every output following the playlist switches,
+        // even to the same file, so a finished pipeline always restarts.
+        if let Some(playlist) = &mut videos.playlist {
+            let due = settings
+                .args
+                .interval
+                .is_some_and(|interval| last_switch.elapsed() >= interval);
+            if due || finished || skip_requested {
+                skip_requested = false;
+                let path = playlist.advance().to_owned();
+                last_switch = Instant::now();
+                for (name, wp) in wallpapers.iter_mut() {
+                    let output = &state.outputs[name];
+                    if videos.follows_playlist(output) {
+                        wp.switch_to(&path, output);
+                        notify(
+                            &mut ipc,
+                            Event::Video {
+                                output: output.name.as_deref().unwrap_or_default(),
+                                file: &path,
+                            },
+                        );
+                    }
                 }
             }
         }
 
-        // Handle audio muting when windows are focused/maximized (opt-in)
-        if args.mute_on_window && enable_audio {
-            if state.muted_for_windows != was_muted {
-                was_muted = state.muted_for_windows;
-                if was_muted {
-                    pipeline.mute();
-                } else {
-                    pipeline.unmute();
+        // Control socket requests get a reply; MPRIS calls were already
+        // acknowledged on the bus.
+        let mut requests: Vec<(Option<ipc::ClientId>, Request)> = ipc
+            .as_mut()
+            .map(|ipc| ipc.read_requests())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(client, request)| (Some(client), request))
+            .collect();
+        if let Some(mpris) = &mpris {
+            requests.extend(mpris.requests().into_iter().map(|r| (None, r)));
+        }
+        for (client, request) in requests {
+            let reply = match request {
+                Request::Pause => {
+                    user_paused = true;
+                    ipc::OK.to_owned()
+                }
+                Request::Resume => {
+                    user_paused = false;
+                    ipc::OK.to_owned()
+                }
+                Request::Mute | Request::Unmute | Request::Volume(_) if !enable_audio => {
+                    ipc::error("audio is disabled; start q6w with --audio")
+                }
+                Request::Mute => {
+                    user_muted = true;
+                    ipc::OK.to_owned()
+                }
+                Request::Unmute => {
+                    user_muted = false;
+                    ipc::OK.to_owned()
+                }
+                Request::Volume(volume) => {
+                    options.volume = volume;
+                    wallpapers.values_mut().for_each(|wp| wp.set_volume(volume));
+                    notify(&mut ipc, Event::Volume(volume));
+                    ipc::OK.to_owned()
+                }
+                Request::Next if videos.playlist.is_none() => ipc::error("no playlist"),
+                Request::Next => {
+                    skip_requested = true;
+                    ipc::OK.to_owned()
                 }
+                Request::Fps(fps) => {
+                    options.fps = fps;
+                    wallpapers.values_mut().for_each(|wp| wp.set_fps(fps));
+                    notify(&mut ipc, Event::Fps(fps));
+                    ipc::OK.to_owned()
+                }
+                Request::SetFile { output, path } => {
+                    if !Path::new(&path).is_file() {
+                        ipc::error(&format!("file not found: {path}"))
+                    } else if let Err(e) = gst_pipeline::file_uri(&path) {
+                        ipc::error(&e)
+                    } else {
+                        videos.set_file(output.as_deref(), path.clone());
+                        for (name, wp) in wallpapers.iter_mut() {
+                            let out = &state.outputs[name];
+                            let out_name = out.name.as_deref().unwrap_or_default();
+                            if output.as_deref().is_none_or(|o| o == out_name) {
+                                wp.set_looping(true);
+                                wp.switch_to(&path, out);
+                                notify(
+                                    &mut ipc,
+                                    Event::Video {
+                                        output: out_name,
+                                        file: &path,
+                                    },
+                                );
+                            }
+                        }
+                        ipc::OK.to_owned()
+                    }
+                }
+                Request::Status => ipc::Status {
+                    paused: user_paused.then_some(Reason::User.as_str()),
+                    muted: mute.reasons().map(Reason::as_str).collect(),
+                    volume: options.volume,
+                    fps: options.fps,
+                    outputs: wallpapers
+                        .iter()
+                        .map(|(name, wp)| {
+                            let output = &state.outputs[name];
+                            ipc::OutputStatus {
+                                name: output.name.as_deref().unwrap_or_default(),
+                                file: &wp.path,
+                                paused: wp.pause.reasons().map(Reason::as_str).collect(),
+                                width: output.width,
+                                height: output.height,
+                            }
+                        })
+                        .collect(),
+                }
+                .to_json(),
+            };
+            if let (Some(ipc), Some(client)) = (&mut ipc, client) {
+                ipc.reply(client, &reply);
+            }
+        }
+
+        let mut reload_requested = false;
+        for signal in signals.pending() {
+            match signal {
+                Signal::Terminate => state.running = false,
+                Signal::TogglePause => user_paused = !user_paused,
+                Signal::ToggleMute if enable_audio => user_muted = !user_muted,
+                Signal::ToggleMute => {}
+                Signal::Reload => reload_requested = true,
+            }
+        }
+        let edited = watcher.as_ref().is_some_and(config::Watcher::changed);
+        if reload_requested || edited {
+            reload(
+                &mut settings,
+                reload_requested,
+                &mut videos,
+                &mut options,
+                &mut wallpapers,
+                &state.outputs,
+                &mut ipc,
+            );
+            state.watch_idle(settings.args.idle_timeout, &qh);
+        }
+        if settings.power.is_empty() {
+            power_monitor = None;
+        } else {
+            power_monitor
+                .get_or_insert_with(|| power::Monitor::new(&settings.args.power_supply_dir))
+                .update();
+        }
+
+        // Muting first: whether a hidden output may pause depends on it.
+        let mut mute_changed = mute.set(Reason::User, user_muted);
+        mute_changed |= mute.set(
+            Reason::Window,
+            enable_audio && settings.window_effect(&state, None).mute,
+        );
+        for wp in wallpapers.values_mut() {
+            match (mute.active(), wp.muted) {
+                (true, false) => wp.mute(),
+                (false, true) => wp.unmute(),
+                _ => {}
+            }
+        }
+        if mute_changed {
+            match mute.reason() {
+                Some(reason) => notify(
+                    &mut ipc,
+                    Event::Muted {
+                        reason: reason.as_str(),
+                    },
+                ),
+                None => notify(&mut ipc, Event::Unmuted),
             }
         }
 
+        // Each output pauses, dims and throttles for what covers its own
+        // screen.
+        let battery = settings.battery_action(power_monitor.as_ref());
+        let idle = Effect {
+            fps: settings.args.idle_fps.filter(|_| state.idle),
+            ..Effect::default()
+        };
+        let caps = idle.combine(Effect {
+            fps: battery.fps,
+            ..Effect::default()
+        });
+        for (&name, wp) in wallpapers.iter_mut() {
+            let output = &state.outputs[&name];
+            let effect = settings.window_effect(&state, Some(name));
+            wp.set_dim(effect.dim.unwrap_or(0.0));
+            wp.set_fps_cap(effect.combine(caps).fps);
+            let changed = update_pause(
+                wp,
+                user_paused,
+                &settings.args,
+                output,
+                &effect,
+                state.idle,
+                battery,
+            );
+            if !changed {
+                continue;
+            }
+            let output = output.name.as_deref().unwrap_or_default();
+            match wp.pause.reason() {
+                Some(reason) => notify(
+                    &mut ipc,
+                    Event::Paused {
+                        output,
+                        reason: reason.as_str(),
+                    },
+                ),
+                None => notify(&mut ipc, Event::Resumed { output }),
+            }
+        }
+
+        if let Some(mpris) = &mpris {
+            let player = audio_owner.and_then(|name| wallpapers.get(&name));
+            mpris.update(
+                player.map(|wp| (wp.path.as_str(), &wp.pipeline)),
+                user_paused,
+                options.volume,
+                videos.playlist.is_some(),
+            );
+        }
+
         conn.flush().ok();
 
-        queue
+        // Events dispatched here (read by another thread, e.g. wgpu's) may
+        // have changed state the loop body has not seen yet.
+        let dispatched = queue
             .dispatch_pending(&mut state)
             .expect("Wayland dispatch error");
 
@@ -265,18 +1620,63 @@ fn main() {
             break;
         }
 
+        // Sleep until something happens; frame callbacks pace transitions.
+        // Wake up for frames held back until their vblank, the next
+        // playlist switch, the next battery check and to notice an output
+        // that stopped asking for frames.
+        let timeout = if dispatched > 0 {
+            Some(Duration::ZERO)
+        } else {
+            let next_switch = settings
+                .args
+                .interval
+                .filter(|_| videos.playlist.is_some())
+                .map(|interval| interval.saturating_sub(last_switch.elapsed()));
+            let next_hide = wallpapers
+                .iter()
+                .filter(|(_, wp)| !wp.hidden)
+                .filter_map(|(name, _)| state.outputs[name].frame_requested)
+                .map(|t| HIDDEN_AFTER.saturating_sub(t.elapsed()))
+                .min();
+            let next_frame = wallpapers
+                .iter()
+                .filter(|(name, _)| state.outputs[*name].wants_frame())
+                .filter_map(|(_, wp)| wp.due)
+                .map(|due| due.saturating_duration_since(Instant::now()))
+                .min();
+            next_switch
+                .into_iter()
+                .chain(next_hide)
+                .chain(next_frame)
+                .chain(power_monitor.as_ref().and_then(power::Monitor::until_check))
+                .min()
+        };
+
         if let Some(guard) = queue.prepare_read() {
-            let fd = guard.connection_fd().as_raw_fd();
-            let mut pfd = libc::pollfd {
-                fd,
-                events: libc::POLLIN,
-                revents: 0,
-            };
+            // Wake for Wayland events, decoded frames, bus messages, IPC
+            // clients, MPRIS calls, signals and config edits.
+            let fds = [guard.connection_fd().as_raw_fd(), waker.fd(), signals.fd()]
+                .into_iter()
+                .chain(ipc.iter().flat_map(ipc::Server::fds))
+                .chain(watcher.as_ref().map(config::Watcher::fd));
+            let mut pfds: Vec<libc::pollfd> = fds
+                .map(|fd| libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
             unsafe {
-                libc::poll(&mut pfd, 1, 8 /* ms */);
+                libc::poll(
+                    pfds.as_mut_ptr(),
+                    pfds.len() as libc::nfds_t,
+                    timeout.map_or(-1, poll_millis),
+                );
             }
             let _ = guard.read();
         }
+        // Whatever woke us is looked at by the next iteration.
+        waker.drain();
 
         queue
             .dispatch_pending(&mut state)
@@ -287,5 +1687,73 @@ fn main() {
         }
     }
 
-    // Pipeline is dropped here → set_state(Null) via Drop impl
+    // Pipelines are dropped here → set_state(Null) via Drop impl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        let cases = [
+            ("500ms", Ok(Duration::from_millis(500))),
+            ("90", Ok(Duration::from_secs(90))),
+            ("90s", Ok(Duration::from_secs(90))),
+            ("10m", Ok(Duration::from_secs(600))),
+            ("1h", Ok(Duration::from_secs(3600))),
+            ("0s", Ok(Duration::ZERO)),
+            ("", Err("expected a duration like 90s or 10m, got ``")),
+            ("m", Err("expected a duration like 90s or 10m, got `m`")),
+            (
+                "1.5m",
+                Err("unknown duration unit in `1.5m` (use ms, s, m or h)"),
+            ),
+            ("-5s", Err("expected a duration like 90s or 10m, got `-5s`")),
+            (
+                "5d",
+                Err("unknown duration unit in `5d` (use ms, s, m or h)"),
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(
+                parse_duration(text),
+                expected.map_err(str::to_owned),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn colors() {
+        let cases = [
+            ("#000000", Ok([0.0, 0.0, 0.0])),
+            ("#ffffff", Ok([1.0, 1.0, 1.0])),
+            ("FF0000", Ok([1.0, 0.0, 0.0])),
+            ("#00ff00", Ok([0.0, 1.0, 0.0])),
+            ("#fff", Err("expected #RRGGBB, got `#fff`")),
+            ("#gg0000", Err("expected #RRGGBB, got `#gg0000`")),
+            ("#ff00000", Err("expected #RRGGBB, got `#ff00000`")),
+            ("#ééé", Err("expected #RRGGBB, got `#ééé`")),
+            ("", Err("expected #RRGGBB, got ``")),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_color(text), expected.map_err(str::to_owned), "{text}");
+        }
+    }
+
+    #[test]
+    fn output_mappings() {
+        let ok = |name: &str, file: &str| Ok((name.to_owned(), PathBuf::from(file)));
+        let cases = [
+            ("DP-1=a.mp4", ok("DP-1", "a.mp4")),
+            ("DP-1=/v/a=b.mp4", ok("DP-1", "/v/a=b.mp4")),
+            ("=a.mp4", Err("expected NAME=FILE, got `=a.mp4`".to_owned())),
+            ("DP-1=", Err("expected NAME=FILE, got `DP-1=`".to_owned())),
+            ("a.mp4", Err("expected NAME=FILE, got `a.mp4`".to_owned())),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_output_mapping(text), expected, "{text}");
+        }
+    }
 }
@@ -0,0 +1,544 @@
+// SPDX-License-Identifier: AGPL-3.0-only
+// Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
+
+//! MPRIS2 (`org.mpris.MediaPlayer2`) on the session bus.
+//!
+//! Lets playerctl, media keys and bar widgets control q6w when it plays
+//! audio.  The player is the output that owns the audio.
+//!
+//! Method calls are dispatched on a GLib main loop in a thread of its own,
+//! which turns them into `ipc::Request`s, queues them and wakes the main
+//! loop; they are then handled exactly like commands from the control
+//! socket.  Property changes are signalled from the main thread.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use gio::glib::{self, Variant, variant::ObjectPath};
+use gio::prelude::*;
+
+use crate::gst_pipeline::Pipeline;
+use crate::ipc::Request;
+use crate::wake::Waker;
+
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT_IFACE: &str = "org.mpris.MediaPlayer2";
+const PLAYER_IFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+const INTROSPECTION: &str = r#"
+<node>
+  <interface name="org.mpris.MediaPlayer2">
+    <method name="Raise"/>
+    <method name="Quit"/>
+    <property name="CanQuit" type="b" access="read"/>
+    <property name="CanRaise" type="b" access="read"/>
+    <property name="HasTrackList" type="b" access="read"/>
+    <property name="Identity" type="s" access="read"/>
+    <property name="SupportedUriSchemes" type="as" access="read"/>
+    <property name="SupportedMimeTypes" type="as" access="read"/>
+  </interface>
+  <interface name="org.mpris.MediaPlayer2.Player">
+    <method name="Next"/>
+    <method name="Previous"/>
+    <method name="Pause"/>
+    <method name="PlayPause"/>
+    <method name="Stop"/>
+    <method name="Play"/>
+    <method name="Seek">
+      <arg direction="in" name="Offset" type="x"/>
+    </method>
+    <method name="SetPosition">
+      <arg direction="in" name="TrackId" type="o"/>
+      <arg direction="in" name="Position" type="x"/>
+    </method>
+    <method name="OpenUri">
+      <arg direction="in" name="Uri" type="s"/>
+    </method>
+    <signal name="Seeked">
+      <arg name="Position" type="x"/>
+    </signal>
+    <property name="PlaybackStatus" type="s" access="read"/>
+    <property name="Rate" type="d" access="readwrite"/>
+    <property name="Metadata" type="a{sv}" access="read"/>
+    <property name="Volume" type="d" access="readwrite"/>
+    <property name="Position" type="x" access="read"/>
+    <property name="MinimumRate" type="d" access="read"/>
+    <property name="MaximumRate" type="d" access="read"/>
+    <property name="CanGoNext" type="b" access="read"/>
+    <property name="CanGoPrevious" type="b" access="read"/>
+    <property name="CanPlay" type="b" access="read"/>
+    <property name="CanPause" type="b" access="read"/>
+    <property name="CanSeek" type="b" access="read"/>
+    <property name="CanControl" type="b" access="read"/>
+  </interface>
+</node>
+"#;
+
+struct Track {
+    path: String,
+    /// Bumped per track so clients see a new `mpris:trackid`.
+    id: u64,
+    length: Option<Duration>,
+    position: Box<dyn Fn() -> Option<Duration> + Send>,
+}
+
+/// What the bus sees; shared with the GDBus thread.
+#[derive(Default)]
+struct Shared {
+    requests: Vec<Request>,
+    track: Option<Track>,
+    /// Paused over IPC, MPRIS or a signal.  Pausing for fullscreen windows,
+    /// idleness and the like doesn't show: PlayPause must toggle something
+    /// it can undo.
+    user_paused: bool,
+    volume: f64,
+    can_go_next: bool,
+    next_track_id: u64,
+}
+
+impl Shared {
+    fn playback_status(&self) -> &'static str {
+        match (&self.track, self.user_paused) {
+            (None, _) => "Stopped",
+            (Some(_), true) => "Paused",
+            (Some(_), false) => "Playing",
+        }
+    }
+
+    fn metadata(&self) -> Variant {
+        let mut map: HashMap<String, Variant> = HashMap::new();
+        if let Some(track) = &self.track {
+            let id = ObjectPath::try_from(format!("/org/q6w/track/{}", track.id))
+                .expect("valid object path");
+            map.insert("mpris:trackid".into(), id.to_variant());
+            let path = std::path::Path::new(&track.path);
+            let title = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| track.path.clone());
+            map.insert("xesam:title".into(), title.to_variant());
+            if let Ok(uri) = glib::filename_to_uri(path, None) {
+                map.insert("xesam:url".into(), uri.to_string().to_variant());
+            }
+            if let Some(length) = track.length {
+                map.insert("mpris:length".into(), micros(length).to_variant());
+            }
+        }
+        map.to_variant()
+    }
+
+    fn player_property(&self, name: &str) -> Variant {
+        match name {
+            "PlaybackStatus" => self.playback_status().to_variant(),
+            "Rate" | "MinimumRate" | "MaximumRate" => 1.0f64.to_variant(),
+            "Metadata" => self.metadata(),
+            "Volume" => self.volume.to_variant(),
+            "Position" => {
+                let position = self.track.as_ref().and_then(|t| (t.position)());
+                position.map_or(0, micros).to_variant()
+            }
+            "CanGoNext" => self.can_go_next.to_variant(),
+            "CanGoPrevious" | "CanSeek" => false.to_variant(),
+            // CanPlay, CanPause, CanControl
+            _ => true.to_variant(),
+        }
+    }
+
+    /// Translate a Player method call into control requests.
+    fn call(&mut self, method: &str, params: &Variant) -> Result<(), String> {
+        match method {
+            "Play" => self.requests.extend([Request::Resume, Request::Unmute]),
+            "Pause" => self.requests.push(Request::Pause),
+            "PlayPause" if self.user_paused => self.requests.push(Request::Resume),
+            "PlayPause" => self.requests.push(Request::Pause),
+            // A wallpaper can't disappear; stopping means still and silent.
+            "Stop" => self.requests.extend([Request::Pause, Request::Mute]),
+            "Next" if self.can_go_next => self.requests.push(Request::Next),
+            "OpenUri" => {
+                let (uri,) = params.get::<(String,)>().ok_or("expected a URI")?;
+                let (path, _) = glib::filename_from_uri(&uri).map_err(|e| e.to_string())?;
+                self.requests.push(Request::SetFile {
+                    output: None,
+                    path: path.to_string_lossy().into_owned(),
+                });
+            }
+            // Next without a playlist, Previous, Seek, SetPosition: no-ops
+            // as the Can* properties advertise.
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+pub struct Mpris {
+    conn: gio::DBusConnection,
+    shared: Arc<Mutex<Shared>>,
+    main_loop: glib::MainLoop,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Mpris {
+    /// Export the player on the session bus as
+    /// `org.mpris.MediaPlayer2.q6w.instance<pid>`.  `waker` is signalled
+    /// when a method call queued requests.
+    pub fn new(volume: f64, waker: &Waker) -> Result<Self, String> {
+        let conn = gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE)
+            .map_err(|e| e.to_string())?;
+        Self::on_connection(conn, volume, waker)
+    }
+
+    /// Export the player on `conn`.
+    fn on_connection(
+        conn: gio::DBusConnection,
+        volume: f64,
+        waker: &Waker,
+    ) -> Result<Self, String> {
+        let shared = Arc::new(Mutex::new(Shared {
+            volume,
+            ..Default::default()
+        }));
+        let ctx = glib::MainContext::new();
+        let main_loop = glib::MainLoop::new(Some(&ctx), false);
+
+        // GDBus dispatches on the context that is the thread default when
+        // the object is registered, so register from the thread itself.
+        let (ready, started) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("q6w-mpris".into())
+            .spawn({
+                let (conn, shared, waker) = (conn.clone(), Arc::clone(&shared), waker.clone());
+                let main_loop = main_loop.clone();
+                move || {
+                    let acquired =
+                        ctx.with_thread_default(|| match export(&conn, &shared, &waker) {
+                            Ok((owner, registrations)) => {
+                                let _ = ready.send(Ok(()));
+                                main_loop.run();
+                                gio::bus_unown_name(owner);
+                                for id in registrations {
+                                    let _ = conn.unregister_object(id);
+                                }
+                            }
+                            Err(e) => {
+                                let _ = ready.send(Err(e));
+                            }
+                        });
+                    if let Err(e) = acquired {
+                        let _ = ready.send(Err(e.to_string()));
+                    }
+                }
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mpris = Mpris {
+            conn,
+            shared,
+            main_loop,
+            thread: Some(thread),
+        };
+        // On failure the thread has already returned; dropping joins it.
+        match started.recv() {
+            Ok(Ok(())) => Ok(mpris),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err("MPRIS thread exited".into()),
+        }
+    }
+
+    /// Requests from method calls since the last call.
+    pub fn requests(&self) -> Vec<Request> {
+        std::mem::take(&mut self.shared.lock().unwrap().requests)
+    }
+
+    /// Publish the player state: `track` is the audio output's video and
+    /// pipeline, if any, `user_paused` whether the user paused playback.
+    /// Only changed properties are signalled.
+    pub fn update(
+        &self,
+        track: Option<(&str, &Pipeline)>,
+        user_paused: bool,
+        volume: f64,
+        can_go_next: bool,
+    ) {
+        let mut changed: HashMap<String, Variant> = HashMap::new();
+        let mut guard = self.shared.lock().unwrap();
+        let shared = &mut *guard;
+        let old_status = shared.playback_status();
+
+        let same_track = matches!(
+            (&track, &shared.track),
+            (Some((path, _)), Some(current)) if current.path == *path
+        );
+        match track {
+            None => {
+                if shared.track.take().is_some() {
+                    changed.insert("Metadata".into(), shared.metadata());
+                }
+            }
+            Some((_, pipeline)) if same_track => {
+                // The length is only known once the pipeline has prerolled.
+                let current = shared.track.as_mut().expect("same track");
+                if current.length.is_none() {
+                    current.length = pipeline.duration();
+                    if current.length.is_some() {
+                        changed.insert("Metadata".into(), shared.metadata());
+                    }
+                }
+            }
+            Some((path, pipeline)) => {
+                shared.next_track_id += 1;
+                shared.track = Some(Track {
+                    path: path.to_owned(),
+                    id: shared.next_track_id,
+                    length: pipeline.duration(),
+                    position: Box::new(pipeline.position_source()),
+                });
+                changed.insert("Metadata".into(), shared.metadata());
+            }
+        }
+
+        shared.user_paused = user_paused;
+        if shared.playback_status() != old_status {
+            changed.insert(
+                "PlaybackStatus".into(),
+                shared.playback_status().to_variant(),
+            );
+        }
+        if shared.volume != volume {
+            shared.volume = volume;
+            changed.insert("Volume".into(), volume.to_variant());
+        }
+        if shared.can_go_next != can_go_next {
+            shared.can_go_next = can_go_next;
+            changed.insert("CanGoNext".into(), can_go_next.to_variant());
+        }
+        drop(guard);
+
+        if !changed.is_empty() {
+            let args = (PLAYER_IFACE, changed, Vec::<String>::new()).to_variant();
+            if let Err(e) = self.conn.emit_signal(
+                None,
+                OBJECT_PATH,
+                "org.freedesktop.DBus.Properties",
+                "PropertiesChanged",
+                Some(&args),
+            ) {
+                eprintln!("q6w: MPRIS: {e}");
+            }
+        }
+    }
+}
+
+impl Drop for Mpris {
+    fn drop(&mut self) {
+        self.main_loop.quit();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Register both interfaces and take the bus name, dispatching on the
+/// thread-default context.
+fn export(
+    conn: &gio::DBusConnection,
+    shared: &Arc<Mutex<Shared>>,
+    waker: &Waker,
+) -> Result<(gio::OwnerId, Vec<gio::RegistrationId>), String> {
+    let node = gio::DBusNodeInfo::for_xml(INTROSPECTION).map_err(|e| e.to_string())?;
+    let mut registrations = Vec::new();
+    for iface in [ROOT_IFACE, PLAYER_IFACE] {
+        let info = node.lookup_interface(iface).expect("interface in XML");
+        let (on_call, on_get, on_set) =
+            (Arc::clone(shared), Arc::clone(shared), Arc::clone(shared));
+        let (call_waker, set_waker) = (waker.clone(), waker.clone());
+        let registered =
+            conn.register_object(OBJECT_PATH, &info)
+                .method_call(move |_, _, _, iface, method, params, invocation| {
+                    let result = match iface {
+                        PLAYER_IFACE => on_call.lock().unwrap().call(method, &params),
+                        // Raise and Quit: CanRaise and CanQuit are false.
+                        _ => Ok(()),
+                    };
+                    call_waker.wake();
+                    match result {
+                        Ok(()) => invocation.return_value(None),
+                        Err(e) => invocation
+                            .return_dbus_error("org.freedesktop.DBus.Error.InvalidArgs", &e),
+                    }
+                })
+                .property(move |_, _, _, iface, name| match iface {
+                    PLAYER_IFACE => on_get.lock().unwrap().player_property(name),
+                    _ => root_property(name),
+                })
+                .set_property(move |_, _, _, _, name, value| match name {
+                    "Volume" => match value.get::<f64>() {
+                        Some(v) => {
+                            let v = v.clamp(0.0, 1.0);
+                            on_set.lock().unwrap().requests.push(Request::Volume(v));
+                            set_waker.wake();
+                            true
+                        }
+                        None => false,
+                    },
+                    // Only 1.0 is supported; accepting keeps clients quiet.
+                    "Rate" => true,
+                    _ => false,
+                })
+                .build();
+        match registered {
+            Ok(id) => registrations.push(id),
+            Err(e) => {
+                for id in registrations {
+                    let _ = conn.unregister_object(id);
+                }
+                return Err(e.to_string());
+            }
+        }
+    }
+
+    let name = format!("org.mpris.MediaPlayer2.q6w.instance{}", std::process::id());
+    let owner = gio::bus_own_name_on_connection(
+        conn,
+        &name,
+        gio::BusNameOwnerFlags::NONE,
+        |_, _| {},
+        |_, name| eprintln!("q6w: MPRIS: lost bus name {name}"),
+    );
+    Ok((owner, registrations))
+}
+
+fn root_property(name: &str) -> Variant {
+    match name {
+        "Identity" => "q6w".to_variant(),
+        "SupportedUriSchemes" => vec!["file"].to_variant(),
+        "SupportedMimeTypes" => vec!["video/mp4", "video/webm", "video/x-matroska"].to_variant(),
+        // CanQuit, CanRaise, HasTrackList
+        _ => false.to_variant(),
+    }
+}
+
+/// MPRIS times are microseconds.
+fn micros(d: Duration) -> i64 {
+    d.as_micros().min(i64::MAX as u128) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+
+    #[test]
+    fn methods_become_requests() {
+        let cases: &[(&str, bool, &[Request])] = &[
+            ("Play", false, &[Request::Resume, Request::Unmute]),
+            ("Pause", false, &[Request::Pause]),
+            ("PlayPause", false, &[Request::Pause]),
+            ("PlayPause", true, &[Request::Resume]),
+            ("Stop", false, &[Request::Pause, Request::Mute]),
+            ("Next", false, &[]),
+            ("Previous", false, &[]),
+        ];
+        for (method, user_paused, expected) in cases {
+            let mut shared = Shared {
+                user_paused: *user_paused,
+                ..Default::default()
+            };
+            shared.call(method, &().to_variant()).unwrap();
+            assert_eq!(shared.requests, *expected, "{method}");
+        }
+
+        let mut shared = Shared {
+            can_go_next: true,
+            ..Default::default()
+        };
+        shared.call("Next", &().to_variant()).unwrap();
+        assert_eq!(shared.requests, [Request::Next]);
+    }
+
+    /// A private `dbus-daemon`, killed on drop.
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        /// `None` if `dbus-daemon` isn't installed.
+        fn start() -> Option<Bus> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.as_mut()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Bus {
+                daemon,
+                address: address.trim().to_owned(),
+            })
+        }
+
+        fn connect(&self) -> gio::DBusConnection {
+            gio::DBusConnection::for_address_sync(
+                &self.address,
+                gio::DBusConnectionFlags::AUTHENTICATION_CLIENT
+                    | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+                None,
+                gio::Cancellable::NONE,
+            )
+            .expect("connect to the private bus")
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    #[test]
+    fn player_on_a_private_bus() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon not found; skipping");
+            return;
+        };
+        let conn = bus.connect();
+        let service = conn.unique_name().expect("unique name").to_string();
+        let waker = Waker::new().unwrap();
+        let mpris = Mpris::on_connection(conn, 0.5, &waker).unwrap();
+        let client = bus.connect();
+        let call = |method: &str| {
+            client
+                .call_sync(
+                    Some(&service),
+                    OBJECT_PATH,
+                    PLAYER_IFACE,
+                    method,
+                    None,
+                    None,
+                    gio::DBusCallFlags::NONE,
+                    5000,
+                    gio::Cancellable::NONE,
+                )
+                .unwrap()
+        };
+        // Only the user's pause counts: whatever else holds playback,
+        // PlayPause must be able to undo it.
+        call("PlayPause");
+        assert_eq!(mpris.requests(), [Request::Pause]);
+        mpris.update(None, true, 0.5, false);
+        call("PlayPause");
+        assert_eq!(mpris.requests(), [Request::Resume]);
+
+        call("Next");
+        assert_eq!(mpris.requests(), []);
+        mpris.update(None, false, 0.5, true);
+        call("Next");
+        assert_eq!(mpris.requests(), [Request::Next]);
+    }
+}
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: AGPL-3.0-only
+// Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
+
+//! Pause and mute arbitration.
+//!
+//! Playback can be held for several reasons at once: the user asked, a
+//! fullscreen window covers the output, the output is hidden, …  A
+//! `Controller` keeps the set of active reasons; playback only resumes once
+//! the set is empty, so one reason going away never undoes another.  Muting
+//! uses the same model.  Nothing here knows about Wayland or GStreamer.
+
+use std::collections::BTreeSet;
+
+/// Why playback is paused or muted.  Declared most explicit first, which is
+/// the order reasons are reported in: a user pause outlasts fullscreen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reason {
+    /// Asked for over IPC, MPRIS or a signal.
+    User,
+    /// The compositor powered the monitor off.
+    PowerOff,
+    /// A fullscreen window covers the output.
+    Fullscreen,
+    /// A focused or maximized window, per `--pause-on-window`,
+    /// `--mute-on-window` or a window rule.
+    Window,
+    /// Nobody has touched the seat for `--idle-timeout`.
+    Idle,
+    /// A `[[power]]` policy while on battery.
+    Battery,
+    /// The output stopped asking for frames.
+    Hidden,
+}
+
+impl Reason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reason::User => "user",
+            Reason::PowerOff => "power-off",
+            Reason::Fullscreen => "fullscreen",
+            Reason::Window => "window",
+            Reason::Idle => "idle",
+            Reason::Battery => "battery",
+            Reason::Hidden => "hidden",
+        }
+    }
+}
+
+/// The set of reasons playback is held for.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Controller {
+    reasons: BTreeSet<Reason>,
+}
+
+impl Controller {
+    /// Add `reason` when `active`, remove it otherwise.  Returns `true` if
+    /// the reported reason changed, i.e. there is something to announce.
+    pub fn set(&mut self, reason: Reason, active: bool) -> bool {
+        let before = self.reason();
+        if active {
+            self.reasons.insert(reason);
+        } else {
+            self.reasons.remove(&reason);
+        }
+        self.reason() != before
+    }
+
+    /// Whether playback is held for any reason.
+    pub fn active(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+
+    /// The most explicit active reason, the one to report.
+    pub fn reason(&self) -> Option<Reason> {
+        self.reasons.first().copied()
+    }
+
+    /// Whether playback is held for `reason`, among others.
+    pub fn contains(&self, reason: Reason) -> bool {
+        self.reasons.contains(&reason)
+    }
+
+    /// Every active reason, most explicit first.
+    pub fn reasons(&self) -> impl Iterator<Item = Reason> + '_ {
+        self.reasons.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reasons set or cleared in order.
+    type Steps = &'static [(Reason, bool)];
+
+    #[test]
+    fn reasons_overlap() {
+        // (steps, active afterwards, reported reason)
+        let cases: &[(Steps, bool, Option<Reason>)] = &[
+            (&[], false, None),
+            (
+                &[(Reason::Fullscreen, true)],
+                true,
+                Some(Reason::Fullscreen),
+            ),
+            (
+                &[(Reason::User, true), (Reason::Fullscreen, true)],
+                true,
+                Some(Reason::User),
+            ),
+            (
+                &[
+                    (Reason::User, true),
+                    (Reason::Fullscreen, true),
+                    (Reason::Fullscreen, false),
+                ],
+                true,
+                Some(Reason::User),
+            ),
+            (
+                &[
+                    (Reason::User, true),
+                    (Reason::Fullscreen, true),
+                    (Reason::User, false),
+                ],
+                true,
+                Some(Reason::Fullscreen),
+            ),
+            (
+                &[(Reason::Hidden, true), (Reason::Hidden, false)],
+                false,
+                None,
+            ),
+            (&[(Reason::Window, false)], false, None),
+        ];
+        for (steps, active, reason) in cases {
+            let mut c = Controller::default();
+            for &(r, on) in *steps {
+                c.set(r, on);
+            }
+            assert_eq!(c.active(), *active, "{steps:?}");
+            assert_eq!(c.reason(), *reason, "{steps:?}");
+        }
+    }
+
+    #[test]
+    fn set_reports_changes_of_the_reported_reason() {
+        let mut c = Controller::default();
+        assert!(c.set(Reason::Hidden, true));
+        assert!(!c.set(Reason::Hidden, true));
+        // A more explicit reason takes over the report.
+        assert!(c.set(Reason::User, true));
+        // A less explicit one hides behind it.
+        assert!(!c.set(Reason::Idle, true));
+        assert!(!c.set(Reason::Idle, false));
+        assert!(c.set(Reason::User, false));
+        assert!(c.set(Reason::Hidden, false));
+        assert!(!c.active());
+    }
+
+    #[test]
+    fn reasons_are_listed_most_explicit_first() {
+        let mut c = Controller::default();
+        for r in [
+            Reason::Hidden,
+            Reason::Battery,
+            Reason::Window,
+            Reason::User,
+            Reason::Idle,
+            Reason::PowerOff,
+            Reason::Fullscreen,
+        ] {
+            c.set(r, true);
+        }
+        let names: Vec<&str> = c.reasons().map(Reason::as_str).collect();
+        assert_eq!(
+            names,
+            [
+                "user",
+                "power-off",
+                "fullscreen",
+                "window",
+                "idle",
+                "battery",
+                "hidden"
+            ]
+        );
+        assert!(c.contains(Reason::Idle));
+    }
+
+    #[test]
+    fn mute_arbitration() {
+        // Muting uses user and window reasons; unmuting by the user leaves
+        // a window mute in place.
+        let mut mute = Controller::default();
+        mute.set(Reason::User, true);
+        mute.set(Reason::Window, true);
+        mute.set(Reason::User, false);
+        assert!(mute.active());
+        assert_eq!(mute.reason(), Some(Reason::Window));
+        mute.set(Reason::Window, false);
+        assert!(!mute.active());
+    }
+}
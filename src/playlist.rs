@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: AGPL-3.0-only
+// Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
+
+//! Playlist of videos loaded from a directory or an M3U file.
+//!
+//! Directories are scanned (non-recursively) for files with a video
+//! extension and played in name order.  M3U entries are played in file
+//! order; relative entries are resolved against the playlist's directory
+//! and `file://` URIs are decoded.
+//! With shuffle on, every pass through the list is a fresh permutation.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use gstreamer as gst;
+
+/// Extensions picked up when scanning a directory.
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mkv", "webm", "mov", "avi", "ogv", "wmv", "flv", "mpg", "mpeg", "ts",
+];
+
+pub struct Playlist {
+    /// Absolute paths, in directory/file order.
+    items: Vec<String>,
+    /// Play order as indices into `items`.
+    order: Vec<usize>,
+    pos: usize,
+    shuffle: bool,
+    rng: u64,
+}
+
+impl Playlist {
+    /// Load the videos from `path`, a directory or an `.m3u`/`.m3u8` file.
+    pub fn load(path: &Path, shuffle: bool) -> Result<Self, String> {
+        let items = if path.is_dir() {
+            Self::scan_dir(path)?
+        } else {
+            Self::read_m3u(path)?
+        };
+        if items.is_empty() {
+            return Err(format!("{}: no videos found", path.display()));
+        }
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+            ^ ((std::process::id() as u64) << 32);
+        let mut playlist = Playlist {
+            order: (0..items.len()).collect(),
+            items,
+            pos: 0,
+            shuffle,
+            rng: seed | 1,
+        };
+        if shuffle {
+            playlist.reshuffle();
+        }
+        Ok(playlist)
+    }
+
+    fn scan_dir(dir: &Path) -> Result<Vec<String>, String> {
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+            })
+            .collect();
+        paths.sort();
+        Ok(paths.iter().map(|p| absolute(p)).collect())
+    }
+
+    fn read_m3u(file: &Path) -> Result<Vec<String>, String> {
+        let text = std::fs::read_to_string(file).map_err(|e| format!("{}: {e}", file.display()))?;
+        let base = file.parent().unwrap_or(Path::new("."));
+        let mut items = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Players write `file://` URIs percent-encoded.
+            let path = if line.starts_with("file:") {
+                match gst::glib::filename_from_uri(line) {
+                    Ok((path, _)) => path,
+                    Err(e) => {
+                        eprintln!("q6w: bad playlist entry, skipping: {line}: {e}");
+                        continue;
+                    }
+                }
+            } else {
+                base.join(line)
+            };
+            if path.is_file() {
+                items.push(absolute(&path));
+            } else {
+                eprintln!("q6w: playlist entry not found, skipping: {line}");
+            }
+        }
+        Ok(items)
+    }
+
+    /// The video currently selected.
+    pub fn current(&self) -> &str {
+        &self.items[self.order[self.pos]]
+    }
+
+    /// Move to the next video and return it.  With shuffle on, a new
+    /// permutation starts after the last one ends.
+    pub fn advance(&mut self) -> &str {
+        self.pos += 1;
+        if self.pos == self.order.len() {
+            self.pos = 0;
+            if self.shuffle {
+                let last = self.order[self.order.len() - 1];
+                self.reshuffle();
+                // Don't play the same video twice in a row across passes.
+                if self.order.len() > 1 && self.order[0] == last {
+                    self.order.swap(0, 1);
+                }
+            }
+        }
+        self.current()
+    }
+
+    /// Fisher–Yates shuffle driven by xorshift64; good enough for picking
+    /// wallpapers and avoids a dependency.
+    fn reshuffle(&mut self) {
+        for i in (1..self.order.len()).rev() {
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            let j = (self.rng % (i as u64 + 1)) as usize;
+            self.order.swap(i, j);
+        }
+    }
+}
+
+fn absolute(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A temporary directory holding empty files, removed on drop.
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new(name: &str, files: &[&str]) -> Tree {
+            let root =
+                std::env::temp_dir().join(format!("q6w-playlist-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            for file in files {
+                let path = root.join(file);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, "").unwrap();
+            }
+            Tree(root.canonicalize().unwrap())
+        }
+
+        fn path(&self, file: &str) -> String {
+            self.0.join(file).to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn playlist(items: &[&str], shuffle: bool, seed: u64) -> Playlist {
+        Playlist {
+            items: items.iter().map(|&s| s.to_owned()).collect(),
+            order: (0..items.len()).collect(),
+            pos: 0,
+            shuffle,
+            rng: seed | 1,
+        }
+    }
+
+    #[test]
+    fn scan_dir() {
+        let tree = Tree::new(
+            "scan",
+            &[
+                "b.mp4",
+                "a.MKV",
+                "c.webm",
+                "notes.txt",
+                "README",
+                "clips.mp4/inside.mp4",
+            ],
+        );
+        let expected = ["a.MKV", "b.mp4", "c.webm"].map(|f| tree.path(f));
+        assert_eq!(Playlist::scan_dir(&tree.0).unwrap(), expected);
+        // The order doesn't depend on the directory's.
+        assert_eq!(Playlist::scan_dir(&tree.0).unwrap(), expected);
+        assert!(Playlist::scan_dir(&tree.0.join("missing")).is_err());
+    }
+
+    #[test]
+    fn read_m3u() {
+        let tree = Tree::new(
+            "m3u",
+            &["a.mp4", "b.mp4", "sub/c.webm", "My Videos/d #1 100%.mp4"],
+        );
+        let list = tree.0.join("list.m3u");
+        let text = format!(
+            "#EXTM3U\n\n  # a comment\n#EXTINF:10,A\na.mp4\nfile://{}\nmissing.mp4\n  \
+             sub/c.webm  \nfile://{}\nfile://{}\nfile:relative.mp4\n",
+            tree.path("b.mp4"),
+            tree.path("My%20Videos/d%20%231%20100%25.mp4"),
+            tree.path("missing.mp4"),
+        );
+        std::fs::write(&list, text).unwrap();
+        assert_eq!(
+            Playlist::read_m3u(&list).unwrap(),
+            ["a.mp4", "b.mp4", "sub/c.webm", "My Videos/d #1 100%.mp4"].map(|f| tree.path(f))
+        );
+    }
+
+    #[test]
+    fn advance_wraps_around() {
+        let mut list = playlist(&["a", "b", "c"], false, 1);
+        assert_eq!(list.current(), "a");
+        let played: Vec<String> = (0..6).map(|_| list.advance().to_owned()).collect();
+        assert_eq!(played, ["b", "c", "a", "b", "c", "a"]);
+
+        let mut single = playlist(&["a"], true, 1);
+        assert_eq!(single.advance(), "a");
+    }
+
+    #[test]
+    fn shuffle_never_repeats_across_passes() {
+        for len in [2, 3, 5] {
+            let items: Vec<String> = (0..len).map(|i| i.to_string()).collect();
+            let names: Vec<&str> = items.iter().map(String::as_str).collect();
+            for seed in 0..64 {
+                let mut playlist = playlist(&names, true, seed);
+                playlist.reshuffle();
+                let mut played = vec![playlist.current().to_owned()];
+                for _ in 1..len * 20 {
+                    played.push(playlist.advance().to_owned());
+                }
+                for pass in played.chunks(len) {
+                    let mut pass = pass.to_vec();
+                    pass.sort();
+                    assert_eq!(pass, items, "seed {seed}: not a permutation");
+                }
+                assert!(
+                    played.windows(2).all(|w| w[0] != w[1]),
+                    "seed {seed}: {played:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,461 @@
+// SPDX-License-Identifier: AGPL-3.0-only
+// Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
+
+//! Battery-aware power policy.
+//!
+//! `[[power]]` sections in the config file say what happens while the
+//! machine runs on battery, optionally only below a charge level:
+//!
+//! ```toml
+//! [[power]]                   # on battery
+//! fps = 30
+//!
+//! [[power]]
+//! below = 20                  # on battery and under 20 %
+//! still = true                # stop decoding, keep the last frame up
+//! ```
+//!
+//! Every section that applies takes effect; together they do the strongest
+//! of each action.  The supplies are read from `/sys/class/power_supply`:
+//! sysfs attributes don't raise inotify events, so they are re-read every
+//! few seconds, and only while there is a policy and a battery to watch.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const SYSFS_ROOT: &str = "/sys/class/power_supply";
+
+/// How often the supplies are re-read.
+const CHECK_EVERY: Duration = Duration::from_secs(5);
+
+/// What a policy does to playback.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Action {
+    /// Pause where the video is.
+    pub pause: bool,
+    /// Stop decoding altogether and keep the last frame on screen; the video
+    /// starts over afterwards.
+    pub still: bool,
+    pub fps: Option<i32>,
+}
+
+impl Action {
+    /// Both actions at once: the stronger of each.
+    fn combine(self, other: Action) -> Action {
+        Action {
+            pause: self.pause || other.pause,
+            still: self.still || other.still,
+            fps: match (self.fps, other.fps) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+
+    /// Whether playback is held, paused or stopped.
+    pub fn halts(&self) -> bool {
+        self.pause || self.still
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    /// Battery percentage the policy starts below; `None` for any.
+    pub below: Option<u8>,
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Policies(pub Vec<Policy>);
+
+impl Policies {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// What the policies that apply to `supply` do together.
+    pub fn action(&self, supply: Supply) -> Action {
+        if !supply.on_battery {
+            return Action::default();
+        }
+        self.0
+            .iter()
+            .filter(|p| {
+                p.below
+                    .is_none_or(|below| supply.level.is_some_and(|l| l < below))
+            })
+            .fold(Action::default(), |action, p| action.combine(p.action))
+    }
+}
+
+/// The state of the machine's power supplies.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Supply {
+    /// Running on battery: there is a battery and no charger is online.
+    pub on_battery: bool,
+    /// Mean charge of the system batteries in percent.
+    pub level: Option<u8>,
+    /// Whether there is a system battery at all.
+    pub has_battery: bool,
+}
+
+impl Supply {
+    /// Read every supply under `root`.  Batteries of peripherals (mice,
+    /// headsets) are skipped; a missing or unreadable tree reads as mains.
+    ///
+    /// Chargers, i.e. mains and USB supplies, decide: many batteries say
+    /// `Not charging` or `Unknown` rather than `Discharging` when unplugged.
+    /// Only without a charger node does the battery status count.
+    pub fn read(root: &Path) -> Supply {
+        let Ok(entries) = std::fs::read_dir(root) else {
+            return Supply::default();
+        };
+        let mut has_charger = false;
+        let mut charger_online = false;
+        let mut discharging = false;
+        let mut has_battery = false;
+        let mut levels = Vec::new();
+        for dir in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+            let attr = |name: &str| {
+                std::fs::read_to_string(dir.join(name))
+                    .map(|s| s.trim().to_owned())
+                    .unwrap_or_default()
+            };
+            if attr("scope") == "Device" {
+                continue;
+            }
+            let kind = attr("type");
+            if kind == "Battery" {
+                has_battery = true;
+                discharging |= attr("status") == "Discharging";
+                if let Ok(level) = attr("capacity").parse::<u8>() {
+                    levels.push(u32::from(level.min(100)));
+                }
+            } else if kind == "Mains" || kind.starts_with("USB") {
+                has_charger = true;
+                charger_online |= attr("online") == "1";
+            }
+        }
+        Supply {
+            on_battery: has_battery
+                && if has_charger {
+                    !charger_online
+                } else {
+                    discharging
+                },
+            level: (!levels.is_empty())
+                .then(|| (levels.iter().sum::<u32>() / levels.len() as u32) as u8),
+            has_battery,
+        }
+    }
+}
+
+/// Re-reads the supplies every `CHECK_EVERY`, as long as there is a
+/// battery.  A machine without one is read once and never wakes the loop.
+pub struct Monitor {
+    root: PathBuf,
+    supply: Supply,
+    next_check: Instant,
+}
+
+impl Monitor {
+    /// Watch the supplies under `root`, normally `SYSFS_ROOT`.
+    pub fn new(root: &Path) -> Self {
+        Monitor {
+            root: root.to_owned(),
+            supply: Supply::read(root),
+            next_check: Instant::now() + CHECK_EVERY,
+        }
+    }
+
+    pub fn supply(&self) -> Supply {
+        self.supply
+    }
+
+    /// Re-read the supplies if it is time.
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        if self.supply.has_battery && now >= self.next_check {
+            self.next_check = now + CHECK_EVERY;
+            self.supply = Supply::read(&self.root);
+        }
+    }
+
+    /// Time until the next re-read, if there will be one.
+    pub fn until_check(&self) -> Option<Duration> {
+        self.supply
+            .has_battery
+            .then(|| self.next_check.saturating_duration_since(Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A supply directory and its attributes.
+    type Dir = (&'static str, &'static [(&'static str, &'static str)]);
+
+    /// A fake `power_supply` tree: one directory per supply holding its
+    /// attributes.  Removed on drop.
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new(name: &str, supplies: &[Dir]) -> Tree {
+            let root =
+                std::env::temp_dir().join(format!("q6w-power-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            for (supply, attrs) in supplies {
+                let dir = root.join(supply);
+                std::fs::create_dir_all(&dir).unwrap();
+                for (attr, value) in *attrs {
+                    std::fs::write(dir.join(attr), format!("{value}\n")).unwrap();
+                }
+            }
+            std::fs::create_dir_all(&root).unwrap();
+            Tree(root)
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const AC_ONLINE: Dir = ("AC", &[("type", "Mains"), ("online", "1")]);
+    const AC_OFFLINE: Dir = ("AC", &[("type", "Mains"), ("online", "0")]);
+
+    const BAT0: Dir = (
+        "BAT0",
+        &[
+            ("type", "Battery"),
+            ("status", "Discharging"),
+            ("capacity", "40"),
+        ],
+    );
+    const BAT0_CHARGING: Dir = (
+        "BAT0",
+        &[
+            ("type", "Battery"),
+            ("status", "Charging"),
+            ("capacity", "40"),
+        ],
+    );
+    const BAT0_NOT_CHARGING: Dir = (
+        "BAT0",
+        &[
+            ("type", "Battery"),
+            ("status", "Not charging"),
+            ("capacity", "40"),
+        ],
+    );
+    const USB_C_ONLINE: Dir = (
+        "ucsi-source-psy-USBC000:001",
+        &[("type", "USB"), ("online", "1")],
+    );
+    const BAT1: Dir = (
+        "BAT1",
+        &[
+            ("type", "Battery"),
+            ("status", "Discharging"),
+            ("capacity", "30"),
+        ],
+    );
+    const MOUSE: Dir = (
+        "hidpp_battery_0",
+        &[
+            ("type", "Battery"),
+            ("scope", "Device"),
+            ("status", "Discharging"),
+            ("capacity", "5"),
+        ],
+    );
+
+    #[test]
+    fn read_supplies() {
+        let cases: &[(&str, &[Dir], Supply)] = &[
+            ("empty", &[], Supply::default()),
+            ("desktop", &[AC_ONLINE], Supply::default()),
+            (
+                "unplugged",
+                &[AC_OFFLINE, BAT0],
+                Supply {
+                    on_battery: true,
+                    level: Some(40),
+                    has_battery: true,
+                },
+            ),
+            (
+                "charging",
+                &[AC_ONLINE, BAT0_CHARGING],
+                Supply {
+                    on_battery: false,
+                    level: Some(40),
+                    has_battery: true,
+                },
+            ),
+            (
+                "two-batteries",
+                &[AC_OFFLINE, BAT0, BAT1],
+                Supply {
+                    on_battery: true,
+                    level: Some(35),
+                    has_battery: true,
+                },
+            ),
+            ("peripheral", &[AC_ONLINE, MOUSE], Supply::default()),
+            // Unplugged, yet the battery doesn't say it discharges.
+            (
+                "not-charging-unplugged",
+                &[AC_OFFLINE, BAT0_NOT_CHARGING],
+                Supply {
+                    on_battery: true,
+                    level: Some(40),
+                    has_battery: true,
+                },
+            ),
+            // Plugged in and held below a charge threshold.
+            (
+                "not-charging-plugged",
+                &[AC_ONLINE, BAT0_NOT_CHARGING],
+                Supply {
+                    on_battery: false,
+                    level: Some(40),
+                    has_battery: true,
+                },
+            ),
+            (
+                "usb-c-charger",
+                &[AC_OFFLINE, USB_C_ONLINE, BAT0],
+                Supply {
+                    on_battery: false,
+                    level: Some(40),
+                    has_battery: true,
+                },
+            ),
+            // No charger node: only the battery status tells.
+            (
+                "no-charger-node",
+                &[BAT0],
+                Supply {
+                    on_battery: true,
+                    level: Some(40),
+                    has_battery: true,
+                },
+            ),
+            (
+                "no-charger-node-idle",
+                &[BAT0_NOT_CHARGING],
+                Supply {
+                    on_battery: false,
+                    level: Some(40),
+                    has_battery: true,
+                },
+            ),
+        ];
+        for (name, supplies, expected) in cases {
+            let tree = Tree::new(name, supplies);
+            assert_eq!(Supply::read(&tree.0), *expected, "{name}");
+        }
+        assert_eq!(
+            Supply::read(Path::new("/nonexistent/power_supply")),
+            Supply::default()
+        );
+    }
+
+    #[test]
+    fn policy_selection() {
+        let policies = Policies(vec![
+            Policy {
+                below: None,
+                action: Action {
+                    fps: Some(30),
+                    ..Action::default()
+                },
+            },
+            Policy {
+                below: Some(50),
+                action: Action {
+                    fps: Some(15),
+                    ..Action::default()
+                },
+            },
+            Policy {
+                below: Some(20),
+                action: Action {
+                    still: true,
+                    ..Action::default()
+                },
+            },
+        ]);
+        let on_battery = |level| Supply {
+            on_battery: true,
+            level,
+            has_battery: true,
+        };
+        let cases = [
+            (
+                Supply {
+                    on_battery: false,
+                    level: Some(10),
+                    has_battery: true,
+                },
+                Action::default(),
+            ),
+            (
+                on_battery(Some(80)),
+                Action {
+                    fps: Some(30),
+                    ..Action::default()
+                },
+            ),
+            // `below` is strict.
+            (
+                on_battery(Some(50)),
+                Action {
+                    fps: Some(30),
+                    ..Action::default()
+                },
+            ),
+            (
+                on_battery(Some(49)),
+                Action {
+                    fps: Some(15),
+                    ..Action::default()
+                },
+            ),
+            (
+                on_battery(Some(10)),
+                Action {
+                    still: true,
+                    fps: Some(15),
+                    ..Action::default()
+                },
+            ),
+            // Unknown level: only the policies without a threshold.
+            (
+                on_battery(None),
+                Action {
+                    fps: Some(30),
+                    ..Action::default()
+                },
+            ),
+        ];
+        for (supply, expected) in cases {
+            assert_eq!(policies.action(supply), expected, "{supply:?}");
+        }
+        assert!(policies.action(on_battery(Some(10))).halts());
+    }
+
+    #[test]
+    fn monitor_only_polls_with_a_battery() {
+        let desktop = Tree::new("monitor-desktop", &[AC_ONLINE]);
+        assert_eq!(Monitor::new(&desktop.0).until_check(), None);
+
+        let laptop = Tree::new("monitor-laptop", &[AC_OFFLINE, BAT0]);
+        let monitor = Monitor::new(&laptop.0);
+        assert!(monitor.supply().on_battery);
+        assert!(monitor.until_check().is_some());
+    }
+}
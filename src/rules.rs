@@ -0,0 +1,225 @@
+// SPDX-License-Identifier: AGPL-3.0-only
+// Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
+
+//! Window rules.
+//!
+//! `[[rule]]` sections in the config file say what an activated or
+//! maximized window does to the wallpaper, picked by its `app_id` and title:
+//!
+//! ```toml
+//! [[rule]]
+//! app-id = "steam_app_*"      # glob: * and ?
+//! pause = true
+//!
+//! [[rule]]
+//! app-id = "foot"             # no actions: never pause or mute
+//!
+//! [[rule]]
+//! app-id = "firefox"
+//! title = "*YouTube*"
+//! mute = true
+//! dim = 0.4                   # darken the video by 40%
+//! fps = 15
+//! ```
+//!
+//! The first matching rule wins.  Windows no rule matches fall back to
+//! `--pause-on-window` and `--mute-on-window`.  Pausing, dimming and the
+//! fps cap apply to the monitor the window is on; muting is global.
+
+/// What a window does while it is activated or maximized.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Effect {
+    pub pause: bool,
+    pub mute: bool,
+    /// How much to darken the video, 0.0 – 1.0.
+    pub dim: Option<f32>,
+    pub fps: Option<i32>,
+}
+
+impl Effect {
+    /// The effect of two windows at once: the stronger of each action.
+    pub fn combine(self, other: Effect) -> Effect {
+        Effect {
+            pause: self.pause || other.pause,
+            mute: self.mute || other.mute,
+            dim: either(self.dim, other.dim, f32::max),
+            fps: either(self.fps, other.fps, i32::min),
+        }
+    }
+}
+
+/// `pick` of both when both are set, otherwise whichever is.
+fn either<T>(a: Option<T>, b: Option<T>, pick: fn(T, T) -> T) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(pick(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    /// Globs; a missing one matches anything.
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    pub effect: Effect,
+}
+
+impl Rule {
+    fn matches(&self, app_id: &str, title: &str) -> bool {
+        self.app_id.as_deref().is_none_or(|p| glob(p, app_id))
+            && self.title.as_deref().is_none_or(|p| glob(p, title))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Rules(pub Vec<Rule>);
+
+impl Rules {
+    /// The effect of the first rule matching the window, if any.
+    pub fn effect(&self, app_id: &str, title: &str) -> Option<Effect> {
+        self.0
+            .iter()
+            .find(|rule| rule.matches(app_id, title))
+            .map(|rule| rule.effect)
+    }
+}
+
+/// Match `text` against `pattern`, where `*` is any run of characters and
+/// `?` exactly one.
+pub fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and the text position it currently covers up to.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            // Mismatch: let the last `*` swallow one more character.
+            _ => match star {
+                Some((sp, st)) => {
+                    p = sp + 1;
+                    t = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        let cases = [
+            ("", "", true),
+            ("", "foot", false),
+            ("*", "", true),
+            ("*", "anything", true),
+            ("foot", "foot", true),
+            ("foot", "footclient", false),
+            ("foot*", "foot", true),
+            ("foot*", "footclient", true),
+            ("foot*", "xfoot", false),
+            ("*foot", "xfoot", true),
+            ("*YouTube*", "Cats - YouTube - Firefox", true),
+            ("*YouTube*", "Cats - Youtube - Firefox", false),
+            ("a*b*c", "a-b-b-c", true),
+            ("a*b*c", "a-b-b-", false),
+            ("a**", "a", true),
+            ("?", "", false),
+            ("?", "a", true),
+            ("?", "ab", false),
+            // `?` is a character, not a byte.
+            ("?", "é", true),
+            ("??", "é", false),
+            ("caf?", "café", true),
+            ("*é", "café", true),
+            ("steam_app_?*", "steam_app_", false),
+            ("steam_app_?*", "steam_app_570", true),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob(pattern, text), expected, "{pattern:?} on {text:?}");
+        }
+    }
+
+    #[test]
+    fn combine() {
+        let none = Effect::default();
+        let pause = Effect {
+            pause: true,
+            dim: Some(0.2),
+            fps: Some(30),
+            ..none
+        };
+        let mute = Effect {
+            mute: true,
+            dim: Some(0.5),
+            fps: Some(15),
+            ..none
+        };
+        let both = Effect {
+            pause: true,
+            mute: true,
+            dim: Some(0.5),
+            fps: Some(15),
+        };
+        assert_eq!(pause.combine(mute), both);
+        assert_eq!(mute.combine(pause), both);
+        assert_eq!(pause.combine(none), pause);
+        assert_eq!(none.combine(mute), mute);
+        assert_eq!(none.combine(none), none);
+    }
+
+    fn rule(app_id: Option<&str>, title: Option<&str>, effect: Effect) -> Rule {
+        Rule {
+            app_id: app_id.map(str::to_owned),
+            title: title.map(str::to_owned),
+            effect,
+        }
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let pause = Effect {
+            pause: true,
+            ..Effect::default()
+        };
+        let mute = Effect {
+            mute: true,
+            ..Effect::default()
+        };
+        let rules = Rules(vec![
+            // No actions: never pause or mute, whatever comes later.
+            rule(Some("foot"), None, Effect::default()),
+            rule(Some("firefox"), Some("*YouTube*"), mute),
+            rule(None, Some("*YouTube*"), pause),
+            rule(Some("*"), None, pause),
+        ]);
+        let cases = [
+            ("foot", "~", Some(Effect::default())),
+            ("firefox", "Cats - YouTube", Some(mute)),
+            ("chromium", "Cats - YouTube", Some(pause)),
+            ("firefox", "Wikipedia", Some(pause)),
+        ];
+        for (app_id, title, expected) in cases {
+            assert_eq!(rules.effect(app_id, title), expected, "{app_id} {title}");
+        }
+
+        // Without a catch-all, unmatched windows are left to the flags.
+        let rules = Rules(rules.0[..3].to_vec());
+        assert_eq!(rules.effect("firefox", "Wikipedia"), None);
+        assert_eq!(Rules::default().effect("foot", ""), None);
+    }
+}
@@ -0,0 +1,90 @@
+// SPDX-License-Identifier: AGPL-3.0-only
+// Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
+
+//! Unix signals, read from a signalfd in the main loop's poll set.
+//!
+//! | Signal           | Action                             |
+//! | ---------------- | ---------------------------------- |
+//! | SIGTERM, SIGINT  | shut down cleanly                  |
+//! | SIGUSR1          | toggle pause                       |
+//! | SIGUSR2          | toggle mute                        |
+//! | SIGHUP           | reload the config and the videos   |
+//!
+//! The signals are blocked so they queue on the signalfd instead of running
+//! their default action.  The mask is inherited by threads created later,
+//! so `Signals::new` must run before GStreamer, wgpu or GDBus start any.
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+const HANDLED: [libc::c_int; 5] = [
+    libc::SIGTERM,
+    libc::SIGINT,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+    libc::SIGHUP,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    Terminate,
+    TogglePause,
+    ToggleMute,
+    Reload,
+}
+
+pub struct Signals {
+    fd: OwnedFd,
+}
+
+impl Signals {
+    /// Block the handled signals on this thread and open a signalfd for them.
+    pub fn new() -> Result<Self, String> {
+        unsafe {
+            let mut mask: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut mask);
+            for sig in HANDLED {
+                libc::sigaddset(&mut mask, sig);
+            }
+            let err = libc::pthread_sigmask(libc::SIG_BLOCK, &mask, std::ptr::null_mut());
+            if err != 0 {
+                return Err(std::io::Error::from_raw_os_error(err).to_string());
+            }
+            let fd = libc::signalfd(-1, &mask, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC);
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error().to_string());
+            }
+            Ok(Signals {
+                fd: OwnedFd::from_raw_fd(fd),
+            })
+        }
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    /// Signals received since the last call, oldest first.
+    pub fn pending(&self) -> Vec<Signal> {
+        let mut signals = Vec::new();
+        loop {
+            let mut info: libc::signalfd_siginfo = unsafe { std::mem::zeroed() };
+            let size = std::mem::size_of::<libc::signalfd_siginfo>();
+            let n = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    (&mut info as *mut libc::signalfd_siginfo).cast(),
+                    size,
+                )
+            };
+            if n != size as isize {
+                return signals;
+            }
+            signals.push(match info.ssi_signo as libc::c_int {
+                libc::SIGUSR1 => Signal::TogglePause,
+                libc::SIGUSR2 => Signal::ToggleMute,
+                libc::SIGHUP => Signal::Reload,
+                _ => Signal::Terminate,
+            });
+        }
+    }
+}
@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: AGPL-3.0-only
+// Copyright (C) 2025 Sreehari Anil <sreehari7102008@gmail.com>
+
+//! Wakes the main loop from other threads.
+//!
+//! GStreamer streaming threads (new frames, bus messages) and the MPRIS
+//! thread write to an eventfd that the main loop polls next to the Wayland
+//! fd, so it can sleep without a timeout while nothing happens.
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::Arc;
+
+/// A cloneable handle to the loop's eventfd.
+#[derive(Clone)]
+pub struct Waker {
+    fd: Arc<OwnedFd>,
+}
+
+impl Waker {
+    pub fn new() -> Result<Self, String> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        Ok(Waker {
+            fd: Arc::new(unsafe { OwnedFd::from_raw_fd(fd) }),
+        })
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    /// Make the next (or current) poll return.  Safe from any thread.
+    pub fn wake(&self) {
+        let one = 1u64;
+        // Only fails when the counter would overflow, i.e. already signalled.
+        unsafe {
+            libc::write(self.fd.as_raw_fd(), (&one as *const u64).cast(), 8);
+        }
+    }
+
+    /// Reset after the loop woke up.
+    pub fn drain(&self) {
+        let mut count = 0u64;
+        unsafe {
+            libc::read(self.fd.as_raw_fd(), (&mut count as *mut u64).cast(), 8);
+        }
+    }
+}